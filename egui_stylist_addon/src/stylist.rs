@@ -9,12 +9,14 @@ pub struct GodotEguiStylist {
     style: StylistState,
     godot_egui: Option<Instance<GodotEgui, Shared>>,
     file_dialog: Option<Ref<FileDialog, Shared>>,
+    /// Whether the file selected in the save dialog is a Godot theme instead of an egui theme.
+    export_godot_theme: bool,
 }
 
 #[methods]
 impl GodotEguiStylist {
     fn new(_: &Control) -> Self {
-        Self { style: StylistState::default(), godot_egui: None, file_dialog: None }
+        Self { style: StylistState::default(), godot_egui: None, file_dialog: None, export_godot_theme: false }
     }
 
     /// Updates egui from the `_gui_input` callback
@@ -93,6 +95,9 @@ impl GodotEguiStylist {
         let fd = unsafe { self.file_dialog.expect("file dialog should be initialized").assume_safe() };
         if fd.mode().0 == FileDialog::MODE_OPEN_FILE {
             self.style.import_theme(load_theme(path));
+        } else if fd.mode().0 == FileDialog::MODE_SAVE_FILE && self.export_godot_theme {
+            let theme = godot_egui::godot_theme::egui_theme_to_godot_theme(self.style.export_theme());
+            godot_egui::godot_theme::save_godot_theme(&path.to_string(), theme);
         } else if fd.mode().0 == FileDialog::MODE_SAVE_FILE {
             save_theme(path, self.style.export_theme());
         } else {
@@ -135,6 +140,18 @@ impl GodotEguiStylist {
                     let mut filters = StringArray::new();
                     filters.push("*.eguitheme; egui theme format".into());
                     fd.set_filters(filters);
+                    self.export_godot_theme = false;
+                    godot_print!("disable input on `GodotEgui`");
+                    gui_owner.set_process_input(false);
+                }
+                if ui.button("Export as Godot Theme").clicked() {
+                    let fd = unsafe { self.file_dialog.expect("file dialog should be initialized").assume_safe() };
+                    fd.set_mode(FileDialog::MODE_SAVE_FILE);
+                    fd.popup_centered(Vector2::new(500.0, 500.0));
+                    let mut filters = StringArray::new();
+                    filters.push("*.tres; Godot theme".into());
+                    fd.set_filters(filters);
+                    self.export_godot_theme = true;
                     godot_print!("disable input on `GodotEgui`");
                     gui_owner.set_process_input(false);
                 }
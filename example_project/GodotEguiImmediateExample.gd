@@ -0,0 +1,31 @@
+extends Control
+
+# Builds an egui UI from GDScript with the immediate-mode API of GodotEguiImmediate.
+const GodotEguiImmediate = preload("res://GodotEguiImmediate.gdns")
+
+onready var egui = $GodotEgui
+var ui = GodotEguiImmediate.new()
+var clicks := 0
+var speed := 1.0
+var show_details := false
+var player_name := "Ferris"
+
+func _process(_delta):
+	ui.begin_frame()
+	ui.window("Immediate mode")
+	ui.label("Hello, %s!" % player_name)
+	player_name = ui.text_edit("name", player_name)
+	ui.horizontal()
+	if ui.button("Click me"):
+		clicks += 1
+	ui.label("Clicked %d times" % clicks)
+	ui.end_horizontal()
+	speed = ui.slider("speed", speed, 0.0, 10.0)
+	show_details = ui.checkbox("Show details", show_details)
+	if show_details:
+		ui.vertical()
+		ui.label("Speed: %.2f" % speed)
+		ui.label("Name length: %d" % player_name.length())
+		ui.end_vertical()
+	ui.end_window()
+	ui.end_frame(egui)
@@ -1,5 +1,6 @@
 use egui::ComboBox;
 use gdnative::prelude::*;
+use godot_egui::textures::GodotTextureHandle;
 use godot_egui::{ext::InputMapExt, GodotEgui};
 mod window;
 use window::GodotEguiWindowExample;
@@ -22,6 +23,8 @@ pub struct GodotEguiExample {
     slider_value: f32,
     icon_1: Ref<Texture>,
     icon_2: Ref<Texture>,
+    /// The handles keep the icons registered with `GodotEgui` for as long as this node lives.
+    icon_handles: Vec<GodotTextureHandle>,
     show_font_settings: bool,
     text_edit_text: String,
 
@@ -47,6 +50,7 @@ impl GodotEguiExample {
             elapsed_time: 0.0,
             icon_1: load_texture("res://icon.png"),
             icon_2: load_texture("res://icon_ferris.png"),
+            icon_handles: vec![],
             show_font_settings: false,
             text_edit_text: "This is a text edit!".to_owned(),
             dynamically_change_pixels_per_point: false,
@@ -70,8 +74,8 @@ impl GodotEguiExample {
             .and_then(|godot_egui| godot_egui.cast_instance::<GodotEgui>())
             .expect("Expected a `GodotEgui` child with the GodotEgui nativescript class.");
         gui.map_mut(|gui, _| {
-            gui.register_godot_texture(self.icon_1.to_owned());
-            gui.register_godot_texture(self.icon_2.to_owned());
+            self.icon_handles.extend(gui.register_godot_texture(self.icon_1.to_owned()));
+            self.icon_handles.extend(gui.register_godot_texture(self.icon_2.to_owned()));
         })
         .expect("this should have worked");
         self.gui = Some(gui.claim());
@@ -167,17 +171,13 @@ impl GodotEguiExample {
                         ui.heading("Or use your custom images");
                         ui.add_space(5.0);
 
-                        // Custom textures are passed in using their `rid`. Make sure the texture resources don't
-                        // get deallocated for as long as egui will be using them.
-                        let icon_1 = unsafe { self.icon_1.assume_safe() }.get_rid();
-                        let icon_2 = unsafe { self.icon_2.assume_safe() }.get_rid();
-
+                        // Custom textures are drawn using the `egui::TextureId` of the handle returned when
+                        // registering them. The handles keep the texture resources alive.
                         ui.horizontal(|ui| {
-                            // The `rid_to_egui_texture_id` function can be used to convert an rid to an
-                            // egui::TextureId
                             for _ in 0..3 {
-                                ui.image(godot_egui::rid_to_egui_texture_id(icon_1), egui::vec2(64.0, 64.0));
-                                ui.image(godot_egui::rid_to_egui_texture_id(icon_2), egui::vec2(64.0, 64.0));
+                                for handle in &self.icon_handles {
+                                    ui.image(handle.texture_id(), egui::vec2(64.0, 64.0));
+                                }
                             }
                         });
 
@@ -186,7 +186,7 @@ impl GodotEguiExample {
                         ui.heading("You can use custom fonts");
                         ui.label(
                             "This example registers two custom fonts. Custom fonts can be registered from the \
-                             Godot Editor by setting font paths. For more control, you can also use \
+                             Godot Editor by adding font files to `custom_fonts`. For more control, you can also use \
                              egui::Context's set_fonts method to register fonts manually.",
                         );
 
@@ -0,0 +1,190 @@
+//! Routes input between several `GodotEgui` nodes that receive their events from `_input` or
+//! `_unhandled_input`, so overlapping nodes don't all react to the same event.
+//!
+//! Every node with `coordinated_input` enabled is registered here while it is inside the scene tree. Pointer events
+//! are only delivered to the topmost node that draws egui content under the pointer, and key events only to the
+//! node that was clicked last.
+//!
+//! Nodes using the `GuiInput` input mode don't need this, since Godot already delivers their events to the topmost
+//! control. Use `pass_through_empty_space` to let events reach the nodes below instead.
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::Rc;
+
+use gdnative::api::{CanvasLayer, InputEventMouse, InputEventMouseButton};
+use gdnative::prelude::*;
+
+/// Whether a `GodotEgui` node should pass an event on to egui.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Route {
+    /// The event is passed to egui.
+    Deliver,
+    /// The event is passed to egui, which is drawn under the pointer, so the event should not reach the game.
+    DeliverAndCapture,
+    /// Another node receives the event.
+    Skip,
+}
+
+struct Registration {
+    instance_id: i64,
+    owner: Ref<Control>,
+    egui_ctx: egui::Context,
+    raw_input: Rc<RefCell<egui::RawInput>>,
+}
+
+#[derive(Default)]
+struct Coordinator {
+    /// The nodes that are inside the scene tree.
+    nodes: Vec<Registration>,
+    /// The last pointer event that was routed, and the node it was routed to. Every node receives the same event,
+    /// so the target is only searched once.
+    last_event: Option<(i64, Option<i64>)>,
+    /// The node that receives the key events.
+    keyboard_owner: Option<i64>,
+}
+
+thread_local! {
+    static COORDINATOR: RefCell<Coordinator> = RefCell::new(Coordinator::default());
+}
+
+/// Adds a node to the coordinator. Called when the node enters the scene tree.
+pub(crate) fn register(owner: TRef<Control>, egui_ctx: &egui::Context, raw_input: &Rc<RefCell<egui::RawInput>>) {
+    COORDINATOR.with(|coordinator| {
+        let mut coordinator = coordinator.borrow_mut();
+        let instance_id = owner.get_instance_id();
+        coordinator.nodes.retain(|node| node.instance_id != instance_id);
+        coordinator.nodes.push(Registration {
+            instance_id,
+            owner: owner.claim(),
+            egui_ctx: egui_ctx.clone(),
+            raw_input: raw_input.clone(),
+        });
+    })
+}
+
+/// Removes a node from the coordinator. Called when the node exits the scene tree.
+pub(crate) fn unregister(owner: TRef<Control>) {
+    COORDINATOR.with(|coordinator| {
+        let mut coordinator = coordinator.borrow_mut();
+        let instance_id = owner.get_instance_id();
+        coordinator.nodes.retain(|node| node.instance_id != instance_id);
+        if coordinator.keyboard_owner == Some(instance_id) {
+            coordinator.keyboard_owner = None;
+        }
+    })
+}
+
+/// Decides whether the given node passes an event on to egui.
+pub(crate) fn route(owner: TRef<Control>, event: TRef<InputEvent>) -> Route {
+    COORDINATOR.with(|coordinator| {
+        let mut coordinator = coordinator.borrow_mut();
+        let instance_id = owner.get_instance_id();
+
+        if let Some(mouse_event) = event.cast::<InputEventMouse>() {
+            let event_id = event.get_instance_id();
+            let target = match coordinator.last_event {
+                Some((last_event_id, target)) if last_event_id == event_id => target,
+                _ => {
+                    let target = coordinator.pointer_target(mouse_event.position());
+                    coordinator.last_event = Some((event_id, target));
+                    coordinator.dispatched(target, event);
+                    target
+                }
+            };
+            return if target == Some(instance_id) { Route::DeliverAndCapture } else { Route::Skip };
+        }
+
+        if event.cast::<InputEventKey>().is_some() {
+            let keyboard_owner = coordinator.keyboard_owner.or_else(|| coordinator.topmost());
+            return if keyboard_owner == Some(instance_id) { Route::Deliver } else { Route::Skip };
+        }
+
+        Route::Deliver
+    })
+}
+
+/// Returns true if egui drew a window or area at the given position in points, or a side, top or bottom panel,
+/// or if it is dragging something with the pointer. Like `egui::Context::is_pointer_over_area`, the `CentralPanel`
+/// doesn't count, since it fills whatever space is left. Areas with the `Background` order count, since the one
+/// drawn by `GodotEgui::update` is only as large as its content.
+pub(crate) fn is_egui_content_at(egui_ctx: &egui::Context, pos: egui::Pos2) -> bool {
+    // Side, top and bottom panels are not areas, but they shrink the space available to the central panel.
+    // `available_rect` is valid here, since `GodotEgui` runs a first frame in `_ready`.
+    egui_ctx.is_using_pointer() || egui_ctx.layer_id_at(pos).is_some() || !egui_ctx.available_rect().contains(pos)
+}
+
+impl Coordinator {
+    /// The topmost node that draws egui content at the given viewport position.
+    fn pointer_target(&self, position: Vector2) -> Option<i64> {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                let owner = unsafe { node.owner.assume_safe() };
+                if !owner.is_visible_in_tree() {
+                    return false;
+                }
+                let offset_position = position - owner.get_global_rect().position;
+                let local = owner.get_global_transform().affine_inverse().basis_xform(offset_position);
+                let size = owner.get_size();
+                if local.x < 0.0 || local.y < 0.0 || local.x >= size.x || local.y >= size.y {
+                    return false;
+                }
+                let points_per_pixel = 1.0 / node.egui_ctx.pixels_per_point();
+                is_egui_content_at(
+                    &node.egui_ctx,
+                    egui::pos2(local.x * points_per_pixel, local.y * points_per_pixel),
+                )
+            })
+            .max_by(|a, b| draw_order(a, b))
+            .map(|node| node.instance_id)
+    }
+
+    /// The node drawn on top of all the others.
+    fn topmost(&self) -> Option<i64> {
+        self.nodes.iter().max_by(|a, b| draw_order(a, b)).map(|node| node.instance_id)
+    }
+
+    /// Updates the other nodes after a pointer event was routed to `target`.
+    fn dispatched(&mut self, target: Option<i64>, event: TRef<InputEvent>) {
+        // The other nodes stop hovering their widgets.
+        for node in self.nodes.iter().filter(|node| Some(node.instance_id) != target) {
+            if node.egui_ctx.input().pointer.has_pointer() {
+                node.raw_input.borrow_mut().events.push(egui::Event::PointerGone);
+            }
+        }
+
+        // Clicking a node gives it the keyboard, and the other nodes stop editing text.
+        let pressed = event.cast::<InputEventMouseButton>().map_or(false, |button| button.is_pressed());
+        if pressed && self.keyboard_owner != target {
+            self.keyboard_owner = target;
+            for node in self.nodes.iter().filter(|node| Some(node.instance_id) != target) {
+                node.egui_ctx.memory().stop_text_input();
+            }
+        }
+    }
+}
+
+/// Orders nodes by the order they are drawn in: first by their `CanvasLayer`, then by their position in the tree.
+fn draw_order(a: &Registration, b: &Registration) -> Ordering {
+    let (a, b) = unsafe { (a.owner.assume_safe(), b.owner.assume_safe()) };
+    canvas_layer(&a).cmp(&canvas_layer(&b)).then_with(|| {
+        if a.is_greater_than(b) {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        }
+    })
+}
+
+/// The layer of the `CanvasLayer` a node is drawn in, or 0 if it is drawn in the viewport.
+fn canvas_layer(node: &Control) -> i64 {
+    let mut parent = node.get_parent();
+    while let Some(node) = parent {
+        let node = unsafe { node.assume_safe() };
+        if let Some(canvas_layer) = node.cast::<CanvasLayer>() {
+            return canvas_layer.layer();
+        }
+        parent = node.get_parent();
+    }
+    0
+}
@@ -1,6 +1,11 @@
 shader_type canvas_item;
 render_mode blend_disabled;
 
+// The combined `modulate` of the `GodotEgui` node and its parents, with the node's opacity applied.
+uniform vec4 egui_modulate : hint_color = vec4(1.0);
+// Font atlases are uploaded as single channel textures that store the coverage of the glyphs.
+uniform bool font_atlas = false;
+
 // From EGUI repo
 vec3 linear_from_srgb(vec3 srgb) {
     bvec3 cutoff = lessThan(srgb, vec3(10.31475));
@@ -32,7 +37,7 @@ vec4 srgba_from_linear(vec4 rgba) {
 }
 
 void fragment(){
-	vec4 texture_rgba = texture(TEXTURE, UV);
+	vec4 texture_rgba = font_atlas ? vec4(texture(TEXTURE, UV).r) : texture(TEXTURE, UV);
     /// Multiply vertex color with texture color (in linear space).
 	COLOR = texture_rgba * srgba_from_linear(COLOR);
 
@@ -40,4 +45,6 @@ void fragment(){
 	// to try to get the colors to match better.
     COLOR = srgba_from_linear(COLOR * texture_rgba) / 255.0;
     COLOR.a = pow(COLOR.a, 1.6); // Empiric nonsense
+
+    COLOR *= egui_modulate;
 }
\ No newline at end of file
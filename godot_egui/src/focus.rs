@@ -0,0 +1,143 @@
+//! Hands the keyboard focus over between egui and Godot's focus system when `GodotEgui` receives its input
+//! through `_gui_input`.
+//!
+//! egui keeps the order of its focusable widgets private, so the first and last widgets are learned from how the
+//! focus moves when Tab is pressed:
+//! - Tabbing past the last egui widget leaves egui without focus, and Godot's focus moves to the next `Control`.
+//! - Entering the node with Tab focuses the first egui widget, and entering it with Shift+Tab focuses the last one.
+//! - Shift+Tab on the first egui widget moves Godot's focus to the previous `Control`.
+use gdnative::api::GlobalConstants;
+use gdnative::prelude::*;
+
+/// The steps taken to focus the last egui widget when the node is entered with Shift+Tab. egui only wraps the
+/// focus around from the first widget to the last one, and does so with a frame of delay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EnterBackwards {
+    /// The first widget is being focused with a Tab.
+    FocusFirst,
+    /// A Shift+Tab was sent to the first widget.
+    WrapToLast,
+    /// egui moves the focus to the last widget during this frame.
+    AwaitLast,
+}
+
+#[derive(Default)]
+pub(crate) struct FocusBridge {
+    /// The first and last egui widgets that take focus, once they are known.
+    first_focus_id: Option<egui::Id>,
+    last_focus_id: Option<egui::Id>,
+    /// Whether a Tab (false) or Shift+Tab (true) was sent to egui since the last frame.
+    frame_tab: Option<bool>,
+    entering_backwards: Option<EnterBackwards>,
+    /// After tabbing past the last widget, egui gives the focus to its first widget on the next frame. This is
+    /// undone, since the focus was handed to Godot.
+    handed_off: bool,
+    /// Events sent to egui on the next frame.
+    pending_events: Vec<egui::Event>,
+}
+
+impl FocusBridge {
+    /// Called for every Tab key press received through `_gui_input`. Returns true if the event moved the focus
+    /// out of egui and must not be passed on to it.
+    pub(crate) fn on_tab(&mut self, owner: &Control, ctx: &egui::Context, shift: bool) -> bool {
+        // Godot would otherwise move the focus on its own.
+        owner.accept_event();
+        let focus = ctx.memory().focus();
+        if shift && focus.is_some() && focus == self.first_focus_id {
+            ctx.memory().stop_text_input();
+            if let Some(previous) = owner.find_prev_valid_focus() {
+                grab_focus_deferred(&unsafe { previous.assume_safe() });
+            }
+            return true;
+        }
+        self.frame_tab = Some(shift);
+        false
+    }
+
+    /// Called when the node gains Godot's focus. When the focus was moved with the keyboard, the first or last
+    /// egui widget is focused depending on the direction.
+    pub(crate) fn on_focus_enter(&mut self, ctx: &egui::Context) {
+        let input = Input::godot_singleton();
+        if input.is_mouse_button_pressed(GlobalConstants::BUTTON_LEFT) {
+            return;
+        }
+        self.handed_off = false;
+        if input.is_key_pressed(GlobalConstants::KEY_SHIFT) {
+            if let Some(last_focus_id) = self.last_focus_id {
+                ctx.memory().request_focus(last_focus_id);
+                return;
+            }
+            self.entering_backwards = Some(EnterBackwards::FocusFirst);
+        }
+        self.push_tab(false);
+        self.frame_tab = Some(false);
+    }
+
+    /// Called when the node loses Godot's focus, so egui stops editing text as well.
+    pub(crate) fn on_focus_exit(&mut self, ctx: &egui::Context) {
+        ctx.memory().stop_text_input();
+    }
+
+    /// The events to send to egui at the start of the next frame.
+    pub(crate) fn take_events(&mut self) -> Vec<egui::Event> {
+        std::mem::take(&mut self.pending_events)
+    }
+
+    /// Called after every egui frame with the widget that had the focus before it.
+    pub(crate) fn end_frame(&mut self, owner: &Control, ctx: &egui::Context, focus_before: Option<egui::Id>) {
+        let focus = ctx.memory().focus();
+        let tab = self.frame_tab.take();
+
+        match self.entering_backwards.take() {
+            Some(EnterBackwards::FocusFirst) => {
+                self.push_tab(true);
+                self.entering_backwards = Some(EnterBackwards::WrapToLast);
+            }
+            Some(EnterBackwards::WrapToLast) => self.entering_backwards = Some(EnterBackwards::AwaitLast),
+            Some(EnterBackwards::AwaitLast) => self.last_focus_id = focus,
+            None => {}
+        }
+
+        match (tab, focus_before, focus) {
+            // Tabbing past the last widget, so the focus goes to the next Godot control.
+            (Some(false), Some(last), None) => {
+                self.last_focus_id = Some(last);
+                if let Some(next) = owner.find_next_valid_focus() {
+                    let next = unsafe { next.assume_safe() };
+                    if next.get_instance_id() != owner.get_instance_id() {
+                        self.handed_off = true;
+                        grab_focus_deferred(&next);
+                        return;
+                    }
+                }
+            }
+            // Tabbing into egui while nothing was focused gives the focus to the first widget.
+            (Some(false), None, Some(first)) => self.first_focus_id = Some(first),
+            _ => {}
+        }
+
+        // Keep Godot's focus in sync with egui's, e.g. when a text field is clicked or focused from code.
+        if focus.is_some() && !owner.has_focus() {
+            if self.handed_off {
+                ctx.memory().stop_text_input();
+                self.handed_off = false;
+            } else {
+                grab_focus_deferred(owner);
+            }
+        }
+    }
+
+    fn push_tab(&mut self, shift: bool) {
+        let modifiers = egui::Modifiers { shift, ..Default::default() };
+        self.pending_events.push(egui::Event::Key { key: egui::Key::Tab, pressed: true, modifiers });
+        self.pending_events.push(egui::Event::Key { key: egui::Key::Tab, pressed: false, modifiers });
+    }
+}
+
+/// Gives Godot's focus to `control` once the `GodotEgui` instance is no longer borrowed. Godot sends the focus
+/// notifications right away, and the node would fail to borrow itself to handle them.
+fn grab_focus_deferred(control: &Control) {
+    unsafe {
+        control.call_deferred("grab_focus", &[]);
+    }
+}
@@ -0,0 +1,360 @@
+//! Fonts set from the Godot editor through the `custom_fonts` property of `GodotEgui`, and the `FontLibrary` used
+//! to manage fonts from code.
+//!
+//! Every entry of `custom_fonts` is one of:
+//! - The path of a `.ttf` or `.otf` file, e.g. `res://fonts/MyFont.ttf`.
+//! - A `DynamicFontData` or `DynamicFont` resource.
+//! - A `Dictionary` with the font as `font`, and optionally a `family` and a `priority`.
+//!
+//! The family is `proportional` (the default), `monospace`, or any other name, which creates a named family that
+//! can be used with `egui::FontFamily::Name`. Within a family, fonts with a higher priority are tried first, and all
+//! custom fonts are tried before egui's default fonts. Fonts with the same priority keep their order in the array.
+//!
+//! # Fallback chains
+//! The `FontLibrary` of a node sets the fonts of each family in order, e.g. a latin font followed by a CJK font and
+//! an emoji font. Large fallback fonts can be lazy: their file is only read the first time the text of a family
+//! that uses them contains a character none of the loaded fonts of the family has. Only the glyphs that are drawn
+//! are added to the font atlas, which is limited by `max_texture_side_length` and rebuilt by egui when it fills up.
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::ops::RangeInclusive;
+
+use gdnative::api::{DynamicFont, DynamicFontData, File};
+use gdnative::prelude::*;
+
+/// A font file added to a `FontLibrary`.
+#[derive(Clone, Debug)]
+pub struct FontSource {
+    path: String,
+    tweak: egui::FontTweak,
+    lazy: bool,
+    ranges: Vec<RangeInclusive<char>>,
+}
+
+impl FontSource {
+    /// The font file at `path`, e.g. `res://fonts/NotoSansJP-Regular.otf`.
+    pub fn new(path: impl Into<String>) -> Self {
+        Self { path: path.into(), tweak: Default::default(), lazy: false, ranges: Vec::new() }
+    }
+
+    /// Scales and moves the glyphs of the font, e.g. to match the size and baseline of the other fonts of a chain.
+    pub fn tweak(mut self, tweak: egui::FontTweak) -> Self {
+        self.tweak = tweak;
+        self
+    }
+
+    /// Only reads the font when a character is missing from the fonts loaded before it in a chain.
+    pub fn lazy(mut self) -> Self {
+        self.lazy = true;
+        self
+    }
+
+    /// Limits the missing characters that load a lazy font, e.g. `'\u{4E00}'..='\u{9FFF}'` for CJK ideographs.
+    /// A lazy font without ranges is loaded for any missing character.
+    pub fn range(mut self, range: RangeInclusive<char>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    fn covers(&self, c: char) -> bool {
+        self.ranges.is_empty() || self.ranges.iter().any(|range| range.contains(&c))
+    }
+}
+
+/// The fonts and fallback chains of a `GodotEgui` node, see `GodotEgui::font_library`. Changes are applied at the
+/// start of the next frame.
+#[derive(Default)]
+pub struct FontLibrary {
+    sources: BTreeMap<String, FontSource>,
+    /// Tweaks for fonts that were not added to the library, like egui's default fonts.
+    tweaks: BTreeMap<String, egui::FontTweak>,
+    chains: BTreeMap<egui::FontFamily, Vec<String>>,
+    /// The lazy fonts that were requested by a missing character.
+    requested: HashSet<String>,
+    /// The fonts whose file could not be read, so the error is only logged once.
+    failed: HashSet<String>,
+    dirty: bool,
+}
+
+impl FontLibrary {
+    /// Adds a font, which is used by the chains that contain its name.
+    pub fn add(&mut self, name: &str, source: FontSource) {
+        self.sources.insert(name.to_owned(), source);
+        self.failed.remove(name);
+        self.dirty = true;
+    }
+
+    /// Sets the scale and offsets of a font, including egui's default fonts and the fonts of `custom_fonts`.
+    pub fn set_tweak(&mut self, name: &str, tweak: egui::FontTweak) {
+        match self.sources.get_mut(name) {
+            Some(source) => source.tweak = tweak,
+            None => {
+                self.tweaks.insert(name.to_owned(), tweak);
+            }
+        }
+        self.dirty = true;
+    }
+
+    /// Sets the fonts of a family, from the one tried first to the last fallback. Names may refer to the fonts of
+    /// this library, egui's default fonts (`Ubuntu-Light`, `NotoEmoji-Regular`, `emoji-icon-font` and `Hack`) and
+    /// the fonts of `custom_fonts`, named after their file.
+    pub fn set_chain(&mut self, family: egui::FontFamily, fonts: Vec<String>) {
+        self.chains.insert(family, fonts);
+        self.dirty = true;
+    }
+
+    /// The fonts of a family, as set with `set_chain`.
+    pub fn chain(&self, family: &egui::FontFamily) -> Option<&[String]> {
+        self.chains.get(family).map(Vec::as_slice)
+    }
+
+    /// Applies the library again on the next frame, after the fonts of the node were replaced.
+    pub(crate) fn invalidate(&mut self) {
+        self.dirty = !self.sources.is_empty() || !self.tweaks.is_empty() || !self.chains.is_empty();
+    }
+
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Applies the library to the current fonts of the node, reading the files of the fonts that are needed.
+    pub(crate) fn build(&mut self, mut fonts: egui::FontDefinitions) -> egui::FontDefinitions {
+        self.dirty = false;
+        for (name, source) in self.sources.iter() {
+            let needed = !source.lazy || self.requested.contains(name);
+            if !needed || self.failed.contains(name) {
+                continue;
+            }
+            match fonts.font_data.get_mut(name) {
+                Some(font_data) => font_data.tweak = source.tweak,
+                None => match read_file(&source.path) {
+                    Some(data) => {
+                        fonts.font_data.insert(name.clone(), egui::FontData::from_owned(data).tweak(source.tweak));
+                    }
+                    None => {
+                        self.failed.insert(name.clone());
+                    }
+                },
+            }
+        }
+        for (name, tweak) in self.tweaks.iter() {
+            if let Some(font_data) = fonts.font_data.get_mut(name) {
+                font_data.tweak = *tweak;
+            }
+        }
+        for (family, chain) in self.chains.iter() {
+            let loaded =
+                chain.iter().filter(|name| fonts.font_data.contains_key(*name)).cloned().collect::<Vec<_>>();
+            if loaded.is_empty() {
+                // egui can't draw a family without fonts, and lazy fonts are only loaded for missing characters.
+                godot_error!("none of the fonts of the {:?} family are loaded: {:?}", family, chain);
+                continue;
+            }
+            fonts.families.insert(family.clone(), loaded);
+        }
+        fonts
+    }
+
+    /// Requests the lazy fonts needed to draw the text of `shapes`. Returns true if a font was requested, so the
+    /// fonts must be rebuilt.
+    pub(crate) fn request_missing(&mut self, ctx: &egui::Context, shapes: &[egui::epaint::ClippedShape]) -> bool {
+        // Only the families whose chain still has lazy fonts to load are checked.
+        let pending = self
+            .chains
+            .iter()
+            .filter(|(_, chain)| chain.iter().any(|name| self.is_pending(name)))
+            .map(|(family, _)| family.clone())
+            .collect::<HashSet<_>>();
+        if pending.is_empty() {
+            return false;
+        }
+
+        let mut missing = BTreeMap::<egui::FontFamily, HashSet<char>>::new();
+        {
+            let fonts = ctx.fonts();
+            let mut fonts = fonts.lock();
+            let mut visit = |galley: &egui::Galley| {
+                for section in galley.job.sections.iter() {
+                    let font_id = &section.format.font_id;
+                    if !pending.contains(&font_id.family) {
+                        continue;
+                    }
+                    let font = fonts.fonts.font(font_id);
+                    let chars = galley.job.text[section.byte_range.clone()]
+                        .chars()
+                        .filter(|c| !c.is_whitespace() && !c.is_control() && !font.characters().contains(c));
+                    missing.entry(font_id.family.clone()).or_default().extend(chars);
+                }
+            };
+            for egui::epaint::ClippedShape(_, shape) in shapes.iter() {
+                visit_text(shape, &mut visit);
+            }
+        }
+
+        let mut requested = false;
+        for (family, chars) in missing.iter().filter(|(_, chars)| !chars.is_empty()) {
+            // The first pending font of the chain that covers a missing character is loaded. If it doesn't have
+            // the character after all, the next one is loaded on a later frame.
+            let next = self.chains[family].iter().find(|name| {
+                self.is_pending(name) && chars.iter().any(|c| self.sources[name.as_str()].covers(*c))
+            });
+            if let Some(name) = next.cloned() {
+                self.requested.insert(name);
+                requested = true;
+            }
+        }
+        self.dirty |= requested;
+        requested
+    }
+
+    /// Whether `name` is a lazy font that was not loaded yet.
+    fn is_pending(&self, name: &str) -> bool {
+        let lazy = self.sources.get(name).map_or(false, |source| source.lazy);
+        lazy && !self.requested.contains(name) && !self.failed.contains(name)
+    }
+}
+
+/// Calls `visit` with the galley of every text shape in `shape`.
+fn visit_text(shape: &egui::Shape, visit: &mut impl FnMut(&egui::Galley)) {
+    match shape {
+        egui::Shape::Text(text) => visit(&text.galley),
+        egui::Shape::Vec(shapes) => shapes.iter().for_each(|shape| visit_text(shape, visit)),
+        _ => {}
+    }
+}
+
+/// A font read from an entry of `custom_fonts`.
+struct CustomFont {
+    name: String,
+    data: Vec<u8>,
+    family: egui::FontFamily,
+    priority: i64,
+}
+
+/// Adds the fonts of `custom_fonts` to `fonts`. Entries that can't be loaded are skipped with an error.
+pub(crate) fn add_custom_fonts(fonts: &mut egui::FontDefinitions, custom_fonts: &VariantArray) {
+    let custom =
+        custom_fonts.iter().enumerate().filter_map(|(idx, entry)| read_entry(idx, &entry)).collect::<Vec<_>>();
+    insert_custom_fonts(fonts, custom);
+}
+
+/// Puts the custom fonts in front of the fonts of their family, ordered by priority and then by their order in
+/// `custom`.
+fn insert_custom_fonts(fonts: &mut egui::FontDefinitions, mut custom: Vec<CustomFont>) {
+    // The sort is stable, so fonts with the same priority keep their order.
+    custom.sort_by_key(|font| Reverse(font.priority));
+    let mut inserted = BTreeMap::<egui::FontFamily, usize>::new();
+    for font in custom.into_iter() {
+        let mut name = font.name;
+        while fonts.font_data.contains_key(&name) {
+            name.push('_');
+        }
+        fonts.font_data.insert(name.clone(), egui::FontData::from_owned(font.data));
+        let index = inserted.entry(font.family.clone()).or_default();
+        fonts.families.entry(font.family).or_default().insert(*index, name);
+        *index += 1;
+    }
+}
+
+fn read_entry(idx: usize, entry: &Variant) -> Option<CustomFont> {
+    let (source, family, priority) = match entry.try_to::<Dictionary>() {
+        Ok(dict) => {
+            let source = dict.get("font").unwrap_or_else(Variant::nil);
+            let family = match dict.get("family").map(|family| family.try_to::<String>()) {
+                None => egui::FontFamily::Proportional,
+                Some(Ok(family)) => parse_family(&family),
+                Some(Err(_)) => {
+                    godot_error!("the family of custom font {} must be a String", idx);
+                    return None;
+                }
+            };
+            let priority = dict.get("priority").and_then(|priority| priority.try_to::<i64>().ok()).unwrap_or(0);
+            (source, family, priority)
+        }
+        Err(_) => (entry.clone(), egui::FontFamily::Proportional, 0),
+    };
+
+    let path = match font_path(&source) {
+        Some(path) => path,
+        None => {
+            godot_error!("custom font {} must be a font path, a DynamicFontData or a DynamicFont", idx);
+            return None;
+        }
+    };
+    let data = read_file(&path)?;
+    let name = GodotString::from(path.as_str()).get_file().get_basename().to_string();
+    Some(CustomFont { name, data, family, priority })
+}
+
+pub(crate) fn parse_family(family: &str) -> egui::FontFamily {
+    match family.to_lowercase().as_str() {
+        "proportional" => egui::FontFamily::Proportional,
+        "monospace" => egui::FontFamily::Monospace,
+        _ => egui::FontFamily::Name(family.into()),
+    }
+}
+
+/// The path of the font file of a font entry.
+fn font_path(source: &Variant) -> Option<String> {
+    if let Ok(path) = source.try_to::<String>() {
+        return Some(path);
+    }
+    if let Ok(font_data) = source.try_to::<Ref<DynamicFontData>>() {
+        return Some(unsafe { font_data.assume_safe() }.font_path().to_string());
+    }
+    let font = source.try_to::<Ref<DynamicFont>>().ok()?;
+    let font_data = unsafe { font.assume_safe() }.font_data()?;
+    Some(unsafe { font_data.assume_safe() }.font_path().to_string())
+}
+
+/// Reads the whole file at `path`, logging an error if it can't be read.
+pub(crate) fn read_file(path: &str) -> Option<Vec<u8>> {
+    let file = File::new();
+    if let Err(err) = file.open(path, File::READ) {
+        godot_error!("failed to open font {}: {}", path, err);
+        return None;
+    }
+    let data = file.get_buffer(file.get_len()).to_vec();
+    file.close();
+    Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn custom_font(name: &str, family: egui::FontFamily, priority: i64) -> CustomFont {
+        CustomFont { name: name.to_owned(), data: Vec::new(), family, priority }
+    }
+
+    #[test]
+    fn custom_fonts_are_ordered_by_priority_then_array_order() {
+        let mut fonts = egui::FontDefinitions::default();
+        let defaults = fonts.families[&egui::FontFamily::Proportional].clone();
+        let custom = vec![
+            custom_font("a", egui::FontFamily::Proportional, 0),
+            custom_font("b", egui::FontFamily::Proportional, 1),
+            custom_font("c", egui::FontFamily::Proportional, 0),
+            custom_font("d", egui::FontFamily::Proportional, 1),
+            custom_font("e", egui::FontFamily::Monospace, -1),
+        ];
+        insert_custom_fonts(&mut fonts, custom);
+
+        let proportional = &fonts.families[&egui::FontFamily::Proportional];
+        assert_eq!(proportional[..4], ["b", "d", "a", "c"]);
+        assert_eq!(proportional[4..], defaults[..]);
+        assert_eq!(fonts.families[&egui::FontFamily::Monospace][0], "e");
+    }
+
+    #[test]
+    fn custom_fonts_with_the_same_name_are_renamed() {
+        let mut fonts = egui::FontDefinitions::default();
+        let custom = vec![
+            custom_font("font", egui::FontFamily::Proportional, 0),
+            custom_font("font", egui::FontFamily::Proportional, 0),
+        ];
+        insert_custom_fonts(&mut fonts, custom);
+
+        assert_eq!(fonts.families[&egui::FontFamily::Proportional][..2], ["font", "font_"]);
+    }
+}
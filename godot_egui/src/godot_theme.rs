@@ -0,0 +1,306 @@
+//! Converts a Godot `Theme` into an egui `Style`, so egui panels match the rest of the game's UI, and an egui
+//! `Style` back into a Godot `Theme`, so native Godot menus match egui.
+//!
+//! These items of the theme are used when it defines them:
+//! - `Panel/panel`: the fill, border, corner radius and margins of windows, and whether the style is dark.
+//! - `Button/normal`, `Button/hover` and `Button/pressed`: the frames of inactive, hovered and active widgets.
+//!   `Button/font_color`, `Button/font_color_hover` and `Button/font_color_pressed` color their text, and the margins
+//!   of `Button/normal` set the button padding.
+//! - `LineEdit/normal`: the background of text edits, and `LineEdit/selection_color` the selected text.
+//! - `Label/font_color`: the color of text.
+//! - The default font, when it is a `DynamicFont`, which is added to the fonts and scales the text styles.
+//!
+//! Only `StyleBoxFlat` style boxes are converted, other style boxes leave the base style unchanged.
+//!
+//! `style_to_godot_theme` creates the same items, plus `Button/disabled`, the `LineEdit` and `TextEdit` focus style
+//! boxes, and the panels and font colors of `PopupMenu` and `PopupPanel`. egui has no disabled visuals, so disabled
+//! buttons are faded out like egui does.
+use gdnative::api::{DynamicFont, GlobalConstants, ResourceSaver, StyleBox, StyleBoxFlat, Theme};
+use gdnative::prelude::*;
+
+use crate::fonts;
+
+/// The name the default font of a theme is added to the font definitions with.
+const THEME_FONT_NAME: &str = "godot_theme_default_font";
+
+/// Converts `theme` into an egui style. The items the theme doesn't define are taken from `base`.
+pub fn style_from_godot_theme(theme: &Theme, base: &egui::Style) -> egui::Style {
+    let mut style = base.clone();
+
+    if let Some(panel) = style_box_flat(theme, "panel", "Panel") {
+        let panel = unsafe { panel.assume_safe() };
+        let window = &mut style.visuals.widgets.noninteractive;
+        window.bg_fill = color(panel.bg_color());
+        window.bg_stroke = border(&panel);
+        style.visuals.window_rounding = rounding(&panel);
+        style.visuals.dark_mode = is_dark(panel.bg_color());
+        style.spacing.window_margin = egui::style::Margin {
+            left: panel.get_margin(GlobalConstants::MARGIN_LEFT) as f32,
+            right: panel.get_margin(GlobalConstants::MARGIN_RIGHT) as f32,
+            top: panel.get_margin(GlobalConstants::MARGIN_TOP) as f32,
+            bottom: panel.get_margin(GlobalConstants::MARGIN_BOTTOM) as f32,
+        };
+    }
+
+    let widgets = &mut style.visuals.widgets;
+    apply_button(theme, &mut widgets.inactive, "normal", "font_color");
+    apply_button(theme, &mut widgets.hovered, "hover", "font_color_hover");
+    apply_button(theme, &mut widgets.active, "pressed", "font_color_pressed");
+    if let Some(button) = style_box_flat(theme, "normal", "Button") {
+        let button = unsafe { button.assume_safe() };
+        style.spacing.button_padding = egui::vec2(
+            button.get_margin(GlobalConstants::MARGIN_LEFT) as f32,
+            button.get_margin(GlobalConstants::MARGIN_TOP) as f32,
+        );
+    }
+
+    if let Some(line_edit) = style_box_flat(theme, "normal", "LineEdit") {
+        style.visuals.extreme_bg_color = color(unsafe { line_edit.assume_safe() }.bg_color());
+    }
+    if theme.has_color("selection_color", "LineEdit") {
+        style.visuals.selection.bg_fill = color(theme.get_color("selection_color", "LineEdit"));
+    }
+    if theme.has_color("font_color", "Label") {
+        style.visuals.widgets.noninteractive.fg_stroke.color = color(theme.get_color("font_color", "Label"));
+    }
+
+    // The text styles keep their proportions, with the body text as large as the default font.
+    if let Some(font) = default_dynamic_font(theme) {
+        let size = unsafe { font.assume_safe() }.size() as f32;
+        let body_size = style.text_styles.get(&egui::TextStyle::Body).map_or(14.0, |font_id| font_id.size);
+        if size > 0.0 && body_size > 0.0 {
+            for font_id in style.text_styles.values_mut() {
+                font_id.size *= size / body_size;
+            }
+        }
+    }
+    style
+}
+
+/// Adds the default font of `theme` to `base`, tried before the other proportional fonts. Returns `None` if the
+/// theme has no default `DynamicFont`.
+pub fn fonts_from_godot_theme(theme: &Theme, base: egui::FontDefinitions) -> Option<egui::FontDefinitions> {
+    let font = default_dynamic_font(theme)?;
+    let font_data = unsafe { font.assume_safe() }.font_data()?;
+    let path = unsafe { font_data.assume_safe() }.font_path().to_string();
+    let data = fonts::read_file(&path)?;
+
+    let mut fonts = base;
+    fonts.font_data.insert(THEME_FONT_NAME.to_owned(), egui::FontData::from_owned(data));
+    let proportional = fonts.families.entry(egui::FontFamily::Proportional).or_default();
+    proportional.retain(|name| name != THEME_FONT_NAME);
+    proportional.insert(0, THEME_FONT_NAME.to_owned());
+    Some(fonts)
+}
+
+/// Saves a style and fonts as an `.eguitheme` file, which can be set as the `EguiTheme` of a `GodotEgui` node or
+/// loaded as a `GodotEguiTheme` resource.
+#[cfg(feature = "theme_support")]
+pub fn save_egui_theme(
+    path: &str, style: egui::Style, font_definitions: egui::FontDefinitions,
+) -> Result<(), String> {
+    use gdnative::api::File;
+
+    let theme = egui_theme::EguiTheme::new(style, font_definitions);
+    let data =
+        ron::to_string(&theme).map_err(|err| format!("Theme could not be serialized due to: {:#?}", err))?;
+    let file = File::new();
+    if let Err(err) = file.open(path, File::WRITE) {
+        return Err(format!("failed to save the theme at {}: {}", path, err));
+    }
+    file.store_string(data);
+    file.close();
+    Ok(())
+}
+
+/// Creates a Godot theme with the colors, frames and margins of `style`.
+pub fn style_to_godot_theme(style: &egui::Style) -> Ref<Theme, Unique> {
+    let theme = Theme::new();
+    let visuals = &style.visuals;
+    let widgets = &visuals.widgets;
+
+    let window = &widgets.noninteractive;
+    let margin = style.spacing.window_margin;
+    let panel = |shadow: egui::epaint::Shadow| {
+        let panel = style_box(window.bg_fill, window.bg_stroke, visuals.window_rounding);
+        set_margins(&panel, margin.left, margin.top, margin.right, margin.bottom);
+        panel.set_shadow_size(shadow.extrusion.round() as i64);
+        panel.set_shadow_color(godot_color(shadow.color));
+        panel
+    };
+    set_style_box(&theme, "panel", "Panel", panel(egui::epaint::Shadow::default()));
+    set_style_box(&theme, "panel", "PopupMenu", panel(visuals.popup_shadow));
+    set_style_box(&theme, "panel", "PopupPanel", panel(visuals.popup_shadow));
+    theme.set_color("font_color", "Label", godot_color(window.text_color()));
+
+    let padding = style.spacing.button_padding;
+    let button = |visuals: &egui::style::WidgetVisuals| {
+        let button = style_box(visuals.bg_fill, visuals.bg_stroke, visuals.rounding);
+        set_margins(&button, padding.x, padding.y, padding.x, padding.y);
+        button
+    };
+    set_style_box(&theme, "normal", "Button", button(&widgets.inactive));
+    set_style_box(&theme, "hover", "Button", button(&widgets.hovered));
+    set_style_box(&theme, "pressed", "Button", button(&widgets.active));
+    set_style_box(&theme, "focus", "Button", button(&widgets.hovered));
+    let disabled = egui::style::WidgetVisuals {
+        bg_fill: faded(widgets.inactive.bg_fill),
+        bg_stroke: egui::Stroke::new(widgets.inactive.bg_stroke.width, faded(widgets.inactive.bg_stroke.color)),
+        ..widgets.inactive
+    };
+    set_style_box(&theme, "disabled", "Button", button(&disabled));
+    theme.set_color("font_color", "Button", godot_color(widgets.inactive.text_color()));
+    theme.set_color("font_color_hover", "Button", godot_color(widgets.hovered.text_color()));
+    theme.set_color("font_color_pressed", "Button", godot_color(widgets.active.text_color()));
+    theme.set_color("font_color_focus", "Button", godot_color(widgets.hovered.text_color()));
+    theme.set_color("font_color_disabled", "Button", godot_color(faded(widgets.inactive.text_color())));
+
+    // egui draws text edits with the background of the inactive widgets darkened to `extreme_bg_color`, and
+    // outlines the focused one with the selection stroke.
+    for node_type in ["LineEdit", "TextEdit"].iter() {
+        let text_field = |stroke: egui::Stroke| {
+            let text_field = style_box(visuals.extreme_bg_color, stroke, widgets.inactive.rounding);
+            set_margins(&text_field, padding.x, padding.y, padding.x, padding.y);
+            text_field
+        };
+        set_style_box(&theme, "normal", node_type, text_field(widgets.inactive.bg_stroke));
+        set_style_box(&theme, "read_only", node_type, text_field(widgets.inactive.bg_stroke));
+        set_style_box(&theme, "focus", node_type, text_field(visuals.selection.stroke));
+        theme.set_color("font_color", *node_type, godot_color(visuals.text_color()));
+        theme.set_color("selection_color", *node_type, godot_color(visuals.selection.bg_fill));
+        theme.set_color("cursor_color", *node_type, godot_color(visuals.selection.stroke.color));
+    }
+
+    set_style_box(&theme, "hover", "PopupMenu", button(&widgets.hovered));
+    theme.set_color("font_color", "PopupMenu", godot_color(widgets.inactive.text_color()));
+    theme.set_color("font_color_hover", "PopupMenu", godot_color(widgets.hovered.text_color()));
+    theme.set_color("font_color_disabled", "PopupMenu", godot_color(faded(widgets.inactive.text_color())));
+    theme
+}
+
+/// Creates a Godot theme from the style of an egui theme, see `style_to_godot_theme`.
+#[cfg(feature = "theme_support")]
+pub fn egui_theme_to_godot_theme(theme: egui_theme::EguiTheme) -> Ref<Theme, Unique> {
+    let (style, ..) = theme.extract();
+    style_to_godot_theme(&style)
+}
+
+/// Saves a Godot theme as a resource, e.g. a `.tres` file.
+pub fn save_godot_theme(path: &str, theme: Ref<Theme, Unique>) {
+    let saver = ResourceSaver::godot_singleton();
+    if let Err(err) = saver.save(path, theme.into_shared(), 0) {
+        godot_error!("failed to save the theme at {}: {}", path, err);
+    }
+}
+
+/// The nearest theme set on `node` or one of its `Control` parents.
+pub(crate) fn find_theme(node: &Control) -> Option<Ref<Theme>> {
+    if let Some(theme) = node.theme() {
+        return Some(theme);
+    }
+    let mut parent = node.get_parent();
+    while let Some(node) = parent {
+        let node = unsafe { node.assume_safe() };
+        if let Some(theme) = node.cast::<Control>().and_then(|control| control.theme()) {
+            return Some(theme);
+        }
+        parent = node.get_parent();
+    }
+    None
+}
+
+/// Sets the frame and text color of a widget state from a style box and font color of `Button`.
+fn apply_button(theme: &Theme, visuals: &mut egui::style::WidgetVisuals, style_box: &str, font_color: &str) {
+    if let Some(button) = style_box_flat(theme, style_box, "Button") {
+        let button = unsafe { button.assume_safe() };
+        visuals.bg_fill = color(button.bg_color());
+        visuals.bg_stroke = border(&button);
+        visuals.rounding = rounding(&button);
+    }
+    if theme.has_color(font_color, "Button") {
+        visuals.fg_stroke.color = color(theme.get_color(font_color, "Button"));
+    }
+}
+
+fn style_box_flat(theme: &Theme, name: &str, node_type: &str) -> Option<Ref<StyleBoxFlat>> {
+    if !theme.has_stylebox(name, node_type) {
+        return None;
+    }
+    let style_box = theme.get_stylebox(name, node_type)?;
+    unsafe { style_box.assume_safe() }.cast::<StyleBoxFlat>().map(|style_box| style_box.claim())
+}
+
+fn default_dynamic_font(theme: &Theme) -> Option<Ref<DynamicFont>> {
+    let font = theme.default_font()?;
+    unsafe { font.assume_safe() }.cast::<DynamicFont>().map(|font| font.claim())
+}
+
+fn style_box(fill: egui::Color32, stroke: egui::Stroke, rounding: egui::Rounding) -> Ref<StyleBoxFlat, Unique> {
+    let style_box = StyleBoxFlat::new();
+    style_box.set_bg_color(godot_color(fill));
+    style_box.set_border_width_all(stroke.width.round() as i64);
+    style_box.set_border_color(godot_color(stroke.color));
+    style_box.set_corner_radius_individual(
+        rounding.nw.round() as i64,
+        rounding.ne.round() as i64,
+        rounding.se.round() as i64,
+        rounding.sw.round() as i64,
+    );
+    style_box
+}
+
+fn set_margins(style_box: &StyleBoxFlat, left: f32, top: f32, right: f32, bottom: f32) {
+    style_box.set_default_margin(GlobalConstants::MARGIN_LEFT, left as f64);
+    style_box.set_default_margin(GlobalConstants::MARGIN_TOP, top as f64);
+    style_box.set_default_margin(GlobalConstants::MARGIN_RIGHT, right as f64);
+    style_box.set_default_margin(GlobalConstants::MARGIN_BOTTOM, bottom as f64);
+}
+
+fn set_style_box(theme: &Theme, name: &str, node_type: &str, style_box: Ref<StyleBoxFlat, Unique>) {
+    theme.set_stylebox(name, node_type, style_box.upcast::<StyleBox>().into_shared());
+}
+
+/// The color egui draws disabled widgets with.
+fn faded(color: egui::Color32) -> egui::Color32 {
+    color.linear_multiply(0.5)
+}
+
+fn godot_color(color: egui::Color32) -> Color {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let as_f32 = |x: u8| x as f32 / u8::MAX as f32;
+    Color::from_rgba(as_f32(r), as_f32(g), as_f32(b), as_f32(a))
+}
+
+fn color(color: Color) -> egui::Color32 {
+    let as_u8 = |x: f32| (x.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8;
+    egui::Color32::from_rgba_unmultiplied(as_u8(color.r), as_u8(color.g), as_u8(color.b), as_u8(color.a))
+}
+
+fn is_dark(color: Color) -> bool {
+    0.299 * color.r + 0.587 * color.g + 0.114 * color.b < 0.5
+}
+
+/// egui draws borders with a single width, so the widest side of the style box is used.
+fn border(style_box: &StyleBoxFlat) -> egui::Stroke {
+    let sides = [
+        GlobalConstants::MARGIN_LEFT,
+        GlobalConstants::MARGIN_TOP,
+        GlobalConstants::MARGIN_RIGHT,
+        GlobalConstants::MARGIN_BOTTOM,
+    ];
+    let width = sides.iter().map(|&side| style_box.get_border_width(side)).max().unwrap_or(0);
+    if width > 0 {
+        egui::Stroke::new(width as f32, color(style_box.border_color()))
+    } else {
+        egui::Stroke::none()
+    }
+}
+
+fn rounding(style_box: &StyleBoxFlat) -> egui::Rounding {
+    egui::Rounding {
+        nw: style_box.get_corner_radius(GlobalConstants::CORNER_TOP_LEFT) as f32,
+        ne: style_box.get_corner_radius(GlobalConstants::CORNER_TOP_RIGHT) as f32,
+        sw: style_box.get_corner_radius(GlobalConstants::CORNER_BOTTOM_LEFT) as f32,
+        se: style_box.get_corner_radius(GlobalConstants::CORNER_BOTTOM_RIGHT) as f32,
+    }
+}
@@ -0,0 +1,145 @@
+//! Native Godot `Control`s laid out inside egui, for the cases where a Godot node is the better tool, e.g. a
+//! `VideoPlayer`, a `RichTextLabel` with BBCode or a `LineEdit` that supports the platform IME.
+//!
+//! ```ignore
+//! let host = gui.native_control_host();
+//! let video_player = self.video_player.clone();
+//! gui.update(owner, None, |ui| {
+//!     ui.heading("Intro");
+//!     host.show(ui, &video_player, egui::vec2(320.0, 180.0));
+//! });
+//! ```
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gdnative::prelude::*;
+
+struct HostedControl {
+    control: Ref<Control>,
+    /// A child of the `GodotEgui` node that clips the hosted control to the visible part of its rect. The hosted
+    /// control is its only child.
+    clipper: Option<Ref<Control>>,
+    /// The rect reserved for the control and the visible part of it, in egui points. Only set when the control
+    /// was shown during the current frame.
+    rect: Option<(egui::Rect, egui::Rect)>,
+}
+
+#[derive(Default)]
+struct HostState {
+    /// The hosted controls, by instance id.
+    controls: HashMap<i64, HostedControl>,
+    /// The visible rects of the controls shown during the last frame, in egui points.
+    visible_rects: Vec<egui::Rect>,
+}
+
+/// Places native Godot `Control`s inside the egui layout.
+#[derive(Clone, Default)]
+pub struct NativeControlHost {
+    state: Rc<RefCell<HostState>>,
+}
+
+impl NativeControlHost {
+    /// Reserves a rect of the given size in the egui layout and moves the control over it at the end of the
+    /// frame. The control is hidden on every frame it is not shown.
+    /// # Note
+    /// The control is reparented under the `GodotEgui` node the first time it is shown. It is always drawn on top
+    /// of egui, so windows that overlap it will be drawn below it.
+    pub fn show(&self, ui: &mut egui::Ui, control: &Ref<Control>, size: impl Into<egui::Vec2>) -> egui::Response {
+        let (rect, response) = ui.allocate_exact_size(size.into(), egui::Sense::hover());
+        let visible_rect = rect.intersect(ui.clip_rect());
+        let instance_id = unsafe { control.assume_safe() }.get_instance_id();
+        let mut state = self.state.borrow_mut();
+        let hosted = state.controls.entry(instance_id).or_insert_with(|| HostedControl {
+            control: control.clone(),
+            clipper: None,
+            rect: None,
+        });
+        hosted.rect = Some((rect, visible_rect));
+        response
+    }
+
+    /// The number of controls that are hosted, whether they are visible or not.
+    pub fn len(&self) -> usize {
+        self.state.borrow().controls.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.borrow().controls.is_empty()
+    }
+
+    /// Moves, resizes and clips the hosted controls to the rects reserved for them during this frame, and hides the
+    /// ones that were not shown.
+    pub(crate) fn end_frame(&self, owner: &Control, pixels_per_point: f32) {
+        let state = &mut *self.state.borrow_mut();
+        let to_pixels = |v: egui::Vec2| Vector2::new(v.x * pixels_per_point, v.y * pixels_per_point);
+        state.visible_rects.clear();
+
+        let mut freed = Vec::new();
+        for (instance_id, hosted) in state.controls.iter_mut() {
+            let clipper = match &hosted.clipper {
+                Some(clipper) => unsafe { clipper.assume_safe() },
+                None => {
+                    let clipper = Control::new();
+                    clipper.set_clip_contents(true);
+                    clipper.set_mouse_filter(Control::MOUSE_FILTER_IGNORE);
+                    let control = unsafe { hosted.control.assume_safe() };
+                    if let Some(parent) = control.get_parent() {
+                        unsafe { parent.assume_safe() }.remove_child(control);
+                    }
+                    clipper.add_child(control, false);
+                    let clipper = clipper.into_shared();
+                    owner.add_child(clipper.clone(), false);
+                    hosted.clipper = Some(clipper.clone());
+                    unsafe { clipper.assume_safe() }
+                }
+            };
+
+            // The control was freed or moved elsewhere by the user, so stop hosting it.
+            let control =
+                match clipper.get_child(0).and_then(|child| unsafe { child.assume_safe() }.cast::<Control>()) {
+                    Some(control) => control,
+                    None => {
+                        clipper.queue_free();
+                        freed.push(*instance_id);
+                        continue;
+                    }
+                };
+
+            match hosted.rect.take() {
+                Some((rect, visible_rect)) if visible_rect.is_positive() => {
+                    clipper.set_position(to_pixels(visible_rect.min.to_vec2()), false);
+                    clipper.set_size(to_pixels(visible_rect.size()), false);
+                    control.set_position(to_pixels(rect.min - visible_rect.min), false);
+                    control.set_size(to_pixels(rect.size()), false);
+                    clipper.show();
+                    state.visible_rects.push(visible_rect);
+                }
+                _ => clipper.hide(),
+            }
+        }
+
+        for instance_id in freed {
+            state.controls.remove(&instance_id);
+        }
+    }
+
+    /// Returns true if a hosted control is visible at the given position in egui points.
+    pub(crate) fn contains(&self, pos: egui::Pos2) -> bool {
+        self.state.borrow().visible_rects.iter().any(|rect| rect.contains(pos))
+    }
+
+    /// Returns true if a hosted control, or one of its children, has the keyboard focus.
+    pub(crate) fn has_focus(&self, owner: &Control) -> bool {
+        let focus_owner = match owner.get_focus_owner() {
+            Some(focus_owner) => focus_owner,
+            None => return false,
+        };
+        self.state
+            .borrow()
+            .controls
+            .values()
+            .filter_map(|hosted| hosted.clipper.as_ref())
+            .any(|clipper| unsafe { clipper.assume_safe() }.is_a_parent_of(unsafe { focus_owner.assume_safe() }))
+    }
+}
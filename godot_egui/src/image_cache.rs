@@ -0,0 +1,225 @@
+//! A cache of Godot textures keyed by their resource path, so images can be drawn directly from egui code
+//! without loading and registering them by hand.
+//!
+//! ```ignore
+//! let images = gui.image_cache();
+//! gui.update(owner, None, |ui| {
+//!     let sword = images.image("res://icons/sword.png");
+//!     ui.add(sword.region().image(sword.size));
+//! });
+//! ```
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gdnative::api::{Engine, ImageTexture, ResourceInteractiveLoader};
+use gdnative::core_types::GodotError;
+use gdnative::prelude::*;
+
+use crate::textures::{GodotTextureHandle, TextureRegion, TextureRegistry};
+
+/// The size of the placeholder returned while an image is loading or could not be loaded.
+const PLACEHOLDER_SIZE: f32 = 16.0;
+
+/// An image returned by the `ImageCache`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CachedImage {
+    /// The id used to draw the image with egui.
+    pub texture_id: egui::TextureId,
+    /// The size of the texture in pixels. For an `AtlasTexture` this is the size of its region and margin.
+    pub size: egui::Vec2,
+    /// The normalized uv coordinates of the image inside the texture drawn with `texture_id`, which is only part
+    /// of it for an `AtlasTexture`.
+    pub uv: egui::Rect,
+    /// False while the placeholder is returned instead of the image.
+    pub is_loaded: bool,
+    /// Where the texture content is drawn inside `size`. See `TextureRegion::content`.
+    content: egui::Rect,
+}
+
+impl CachedImage {
+    fn new(region: TextureRegion, is_loaded: bool) -> Self {
+        let TextureRegion { texture_id, uv, size, content } = region;
+        Self { texture_id, size, uv, is_loaded, content }
+    }
+
+    /// The region of the texture to draw, which resolves the region and margin of an `AtlasTexture`.
+    pub fn region(&self) -> TextureRegion {
+        TextureRegion { texture_id: self.texture_id, uv: self.uv, size: self.size, content: self.content }
+    }
+}
+
+enum EntryState {
+    Loading(Ref<ResourceInteractiveLoader>),
+    Loaded(GodotTextureHandle),
+    Failed,
+}
+
+struct Entry {
+    state: EntryState,
+    last_used_frame: u64,
+}
+
+struct ImageCacheState {
+    registry: Rc<RefCell<TextureRegistry>>,
+    entries: HashMap<String, Entry>,
+    placeholder: Option<GodotTextureHandle>,
+    frame: u64,
+    /// The engine frame the cache was last advanced in, since the nodes of a resource group share the cache.
+    advanced_in_frame: Option<i64>,
+    /// Whether an image finished loading when the cache was last advanced.
+    finished_loading: bool,
+    max_unused_frames: u64,
+    interactive_loading: bool,
+}
+
+/// A cache of textures loaded through the `ResourceLoader`.
+#[derive(Clone)]
+pub struct ImageCache {
+    state: Rc<RefCell<ImageCacheState>>,
+}
+
+impl ImageCache {
+    pub(crate) fn new(registry: Rc<RefCell<TextureRegistry>>) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(ImageCacheState {
+                registry,
+                entries: HashMap::new(),
+                placeholder: None,
+                frame: 0,
+                advanced_in_frame: None,
+                finished_loading: false,
+                max_unused_frames: 300,
+                interactive_loading: false,
+            })),
+        }
+    }
+
+    /// Returns the image at the given resource path, loading it the first time it is requested. A placeholder is
+    /// returned while the image is loading or when it could not be loaded.
+    pub fn image(&self, path: &str) -> CachedImage {
+        let state = &mut *self.state.borrow_mut();
+        let frame = state.frame;
+        if !state.entries.contains_key(path) {
+            let entry_state = Self::start_loading(state, path);
+            state.entries.insert(path.to_owned(), Entry { state: entry_state, last_used_frame: frame });
+        }
+        let entry = state.entries.get_mut(path).expect("entry was just inserted");
+        entry.last_used_frame = frame;
+        if let EntryState::Loaded(handle) = &entry.state {
+            return CachedImage::new(handle.region(), true);
+        }
+        Self::placeholder(state)
+    }
+
+    /// The number of images that are loaded or loading.
+    pub fn len(&self) -> usize {
+        self.state.borrow().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.borrow().entries.is_empty()
+    }
+
+    /// Removes every image from the cache.
+    pub fn clear(&self) {
+        self.state.borrow_mut().entries.clear();
+    }
+
+    /// Sets how many frames an image may go unused before it is evicted, and whether images are loaded over
+    /// several frames with a `ResourceInteractiveLoader` instead of blocking on the first request.
+    pub(crate) fn configure(&self, max_unused_frames: u64, interactive_loading: bool) {
+        let mut state = self.state.borrow_mut();
+        state.max_unused_frames = max_unused_frames;
+        state.interactive_loading = interactive_loading;
+    }
+
+    /// Advances the interactive loaders and evicts the images that have not been used recently. Returns true if
+    /// an image finished loading and egui should repaint. The cache is only advanced once per engine frame, so
+    /// every node sharing it can call this at the end of its frame.
+    pub(crate) fn end_frame(&self) -> bool {
+        let state = &mut *self.state.borrow_mut();
+        let engine_frame = Engine::godot_singleton().get_frames_drawn();
+        if state.advanced_in_frame == Some(engine_frame) {
+            return state.finished_loading;
+        }
+        state.advanced_in_frame = Some(engine_frame);
+        let mut finished_loading = false;
+        for (path, entry) in state.entries.iter_mut() {
+            let loader = match &entry.state {
+                EntryState::Loading(loader) => loader.clone(),
+                _ => continue,
+            };
+            let loader = unsafe { loader.assume_safe() };
+            match loader.poll() {
+                Ok(()) => {}
+                Err(GodotError::FileEof) => {
+                    let texture = loader.get_resource().and_then(|resource| resource.cast::<Texture>());
+                    entry.state =
+                        match texture.and_then(|texture| GodotTextureHandle::new(&state.registry, texture)) {
+                            Some(handle) => {
+                                finished_loading = true;
+                                EntryState::Loaded(handle)
+                            }
+                            None => {
+                                godot_error!("{} is not a texture that can be drawn", path);
+                                EntryState::Failed
+                            }
+                        };
+                }
+                Err(err) => {
+                    godot_error!("failed to load {}: {:?}", path, err);
+                    entry.state = EntryState::Failed;
+                }
+            }
+        }
+
+        let (frame, max_unused_frames) = (state.frame, state.max_unused_frames);
+        state.entries.retain(|_, entry| frame.saturating_sub(entry.last_used_frame) <= max_unused_frames);
+        state.frame += 1;
+        state.finished_loading = finished_loading;
+        finished_loading
+    }
+
+    fn start_loading(state: &ImageCacheState, path: &str) -> EntryState {
+        let resource_loader = ResourceLoader::godot_singleton();
+        if !resource_loader.exists(path, "Texture") {
+            godot_error!("image {} does not exist", path);
+            return EntryState::Failed;
+        }
+        if state.interactive_loading {
+            match resource_loader.load_interactive(path, "Texture") {
+                Some(loader) => EntryState::Loading(loader),
+                None => {
+                    godot_error!("failed to load {}", path);
+                    EntryState::Failed
+                }
+            }
+        } else {
+            let texture =
+                resource_loader.load(path, "Texture", false).and_then(|resource| resource.cast::<Texture>());
+            match texture.and_then(|texture| GodotTextureHandle::new(&state.registry, texture)) {
+                Some(handle) => EntryState::Loaded(handle),
+                None => {
+                    godot_error!("failed to load {} as a texture", path);
+                    EntryState::Failed
+                }
+            }
+        }
+    }
+
+    fn placeholder(state: &mut ImageCacheState) -> CachedImage {
+        let registry = &state.registry;
+        let handle = state.placeholder.get_or_insert_with(|| {
+            let image = Image::new();
+            image.create(2, 2, false, Image::FORMAT_RGBA8);
+            image.fill(Color::from_rgba(0.5, 0.5, 0.5, 0.5));
+            let texture = ImageTexture::new();
+            texture.create_from_image(image, 0);
+            GodotTextureHandle::new(registry, texture.upcast::<Texture>().into_shared())
+                .expect("an ImageTexture with an image has a rid")
+        });
+        let size = egui::vec2(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE);
+        CachedImage::new(TextureRegion::full(handle.texture_id(), size), false)
+    }
+}
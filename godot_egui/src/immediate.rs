@@ -0,0 +1,322 @@
+//! An immediate-mode API for building egui UIs from GDScript, without writing a `NativeClass` in Rust.
+//!
+//! `GodotEguiImmediate` records the widgets added between `begin_frame` and `end_frame`, and draws them with a
+//! `GodotEgui` node when the frame ends:
+//!
+//! ```gdscript
+//! var ui = preload("res://GodotEguiImmediate.gdns").new()
+//!
+//! func _process(_delta):
+//!     ui.begin_frame()
+//!     ui.window("Settings")
+//!     ui.label("Hello from GDScript")
+//!     speed = ui.slider("speed", speed, 0.0, 10.0)
+//!     if ui.button("Reset"):
+//!         speed = 1.0
+//!     ui.end_window()
+//!     ui.end_frame($GodotEgui)
+//! ```
+//!
+//! Since the widgets are only drawn in `end_frame`, the interactions are returned one frame late: a click or a
+//! changed value is kept by the id of the widget and returned by the call for the same id on the next frame.
+//! Buttons and checkboxes are identified by their text and the titles of the windows around them, the other
+//! widgets by the id they are given.
+//!
+//! Windows and the `horizontal` and `vertical` layouts are scopes, which contain the widgets added until the
+//! matching `end_window`, `end_horizontal` or `end_vertical` call. Widgets outside of any window fill the
+//! `GodotEgui` node.
+use std::collections::{HashMap, HashSet};
+
+use gdnative::prelude::*;
+
+use crate::GodotEgui;
+
+/// A widget or scope recorded during a frame.
+enum Command {
+    Label(String),
+    Button { id: String, text: String },
+    Checkbox { id: String, text: String, value: bool },
+    Slider { id: String, value: f64, min: f64, max: f64 },
+    TextEdit { id: String, text: String },
+    Window { title: String, children: Vec<Command> },
+    Horizontal(Vec<Command>),
+    Vertical(Vec<Command>),
+}
+
+/// A scope that is still being recorded.
+enum Scope {
+    Window(String),
+    Horizontal,
+    Vertical,
+}
+
+impl Scope {
+    fn name(&self) -> &'static str {
+        match self {
+            Scope::Window(_) => "window",
+            Scope::Horizontal => "horizontal",
+            Scope::Vertical => "vertical",
+        }
+    }
+
+    fn into_command(self, children: Vec<Command>) -> Command {
+        match self {
+            Scope::Window(title) => Command::Window { title, children },
+            Scope::Horizontal => Command::Horizontal(children),
+            Scope::Vertical => Command::Vertical(children),
+        }
+    }
+}
+
+/// A value changed by the user, by the id of its widget.
+enum Value {
+    Bool(bool),
+    Float(f64),
+    Text(String),
+}
+
+/// The interactions of the last drawn frame.
+#[derive(Default)]
+struct Interactions {
+    clicked: HashSet<String>,
+    changed: HashMap<String, Value>,
+}
+
+impl Interactions {
+    fn clear(&mut self) {
+        self.clicked.clear();
+        self.changed.clear();
+    }
+}
+
+/// Builds egui UIs from GDScript. See the `immediate` module.
+#[derive(NativeClass)]
+#[inherit(Reference)]
+pub struct GodotEguiImmediate {
+    commands: Vec<Command>,
+    scopes: Vec<(Scope, Vec<Command>)>,
+    interactions: Interactions,
+}
+
+#[methods]
+impl GodotEguiImmediate {
+    fn new(_owner: &Reference) -> Self {
+        Self { commands: Vec::new(), scopes: Vec::new(), interactions: Interactions::default() }
+    }
+
+    /// Starts recording a frame, discarding anything recorded since the last `end_frame`.
+    #[export]
+    pub fn begin_frame(&mut self, _owner: &Reference) {
+        self.commands.clear();
+        self.scopes.clear();
+    }
+
+    /// Draws the recorded frame with `egui`, which must be a `GodotEgui` node. Scopes that are still open are
+    /// closed.
+    #[export]
+    pub fn end_frame(&mut self, _owner: &Reference, egui: Ref<Control>) {
+        while let Some((scope, children)) = self.scopes.pop() {
+            godot_warn!("the {} scope was not ended before end_frame", scope.name());
+            self.push(scope.into_command(children));
+        }
+        let commands = std::mem::take(&mut self.commands);
+        let egui = unsafe { egui.assume_safe() };
+        let instance = match egui.cast_instance::<GodotEgui>() {
+            Some(instance) => instance,
+            None => {
+                godot_error!("end_frame must be called with a GodotEgui node");
+                return;
+            }
+        };
+        // Interactions that weren't read during this frame are discarded.
+        self.interactions.clear();
+        let interactions = &mut self.interactions;
+        let drawn = instance.map_mut(|egui, owner| {
+            egui.update_ctx(owner.as_ref(), |ctx| show_frame(ctx, &commands, interactions));
+        });
+        if let Err(err) = drawn {
+            godot_error!("failed to draw the frame: {:?}", err);
+        }
+    }
+
+    /// Adds a line of text.
+    #[export]
+    pub fn label(&mut self, _owner: &Reference, text: String) {
+        self.push(Command::Label(text));
+    }
+
+    /// Adds a button. Returns true if it was clicked on the last frame.
+    #[export]
+    pub fn button(&mut self, _owner: &Reference, text: String) -> bool {
+        let id = self.scoped_id(&text);
+        let clicked = self.interactions.clicked.remove(&id);
+        self.push(Command::Button { id, text });
+        clicked
+    }
+
+    /// Adds a checkbox showing `value`. Returns the value, or the value the user changed it to on the last frame.
+    #[export]
+    pub fn checkbox(&mut self, _owner: &Reference, text: String, value: bool) -> bool {
+        let id = self.scoped_id(&text);
+        let value = match self.interactions.changed.remove(&id) {
+            Some(Value::Bool(changed)) => changed,
+            _ => value,
+        };
+        self.push(Command::Checkbox { id, text, value });
+        value
+    }
+
+    /// Adds a slider between `min` and `max` showing `value`. Returns the value, or the value the user changed it
+    /// to on the last frame.
+    #[export]
+    pub fn slider(&mut self, _owner: &Reference, id: String, value: f64, min: f64, max: f64) -> f64 {
+        let value = match self.interactions.changed.remove(&id) {
+            Some(Value::Float(changed)) => changed,
+            _ => value,
+        };
+        self.push(Command::Slider { id, value, min, max });
+        value
+    }
+
+    /// Adds a single line text field showing `text`. Returns the text, or the text the user changed it to on the
+    /// last frame.
+    #[export]
+    pub fn text_edit(&mut self, _owner: &Reference, id: String, text: String) -> String {
+        let text = match self.interactions.changed.remove(&id) {
+            Some(Value::Text(changed)) => changed,
+            _ => text,
+        };
+        self.push(Command::TextEdit { id, text: text.clone() });
+        text
+    }
+
+    /// Starts a window, which contains the widgets added until `end_window`.
+    #[export]
+    pub fn window(&mut self, _owner: &Reference, title: String) {
+        self.scopes.push((Scope::Window(title), Vec::new()));
+    }
+
+    #[export]
+    pub fn end_window(&mut self, _owner: &Reference) {
+        self.end_scope("window");
+    }
+
+    /// Starts laying out widgets from left to right, until `end_horizontal`.
+    #[export]
+    pub fn horizontal(&mut self, _owner: &Reference) {
+        self.scopes.push((Scope::Horizontal, Vec::new()));
+    }
+
+    #[export]
+    pub fn end_horizontal(&mut self, _owner: &Reference) {
+        self.end_scope("horizontal");
+    }
+
+    /// Starts laying out widgets from top to bottom, until `end_vertical`.
+    #[export]
+    pub fn vertical(&mut self, _owner: &Reference) {
+        self.scopes.push((Scope::Vertical, Vec::new()));
+    }
+
+    #[export]
+    pub fn end_vertical(&mut self, _owner: &Reference) {
+        self.end_scope("vertical");
+    }
+
+    /// Adds a command to the innermost open scope.
+    fn push(&mut self, command: Command) {
+        match self.scopes.last_mut() {
+            Some((_, children)) => children.push(command),
+            None => self.commands.push(command),
+        }
+    }
+
+    fn end_scope(&mut self, name: &str) {
+        if self.scopes.last().map_or(true, |(scope, _)| scope.name() != name) {
+            godot_error!("end_{} was called without a matching {}", name, name);
+            return;
+        }
+        if let Some((scope, children)) = self.scopes.pop() {
+            self.push(scope.into_command(children));
+        }
+    }
+
+    /// The id of a widget identified by its text, which includes the titles of the windows around it.
+    fn scoped_id(&self, text: &str) -> String {
+        let mut id = String::new();
+        for (scope, _) in &self.scopes {
+            if let Scope::Window(title) = scope {
+                id.push_str(title);
+                id.push('/');
+            }
+        }
+        id.push_str(text);
+        id
+    }
+}
+
+/// Draws the windows as egui windows, and the other widgets in a panel that fills the node.
+fn show_frame(ctx: &egui::Context, commands: &[Command], interactions: &mut Interactions) {
+    let is_window = |command: &&Command| matches!(command, Command::Window { .. });
+    if commands.iter().any(|command| !is_window(&command)) {
+        let frame = egui::Frame::none().inner_margin(egui::style::Margin::same(10.0));
+        egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+            for command in commands.iter().filter(|command| !is_window(command)) {
+                show_command(ui, command, interactions);
+            }
+        });
+    }
+    for command in commands.iter().filter(is_window) {
+        if let Command::Window { title, children } = command {
+            show_window(ctx, title, children, interactions);
+        }
+    }
+}
+
+fn show_window(ctx: &egui::Context, title: &str, children: &[Command], interactions: &mut Interactions) {
+    egui::Window::new(title).show(ctx, |ui| show_commands(ui, children, interactions));
+}
+
+fn show_commands(ui: &mut egui::Ui, commands: &[Command], interactions: &mut Interactions) {
+    for command in commands {
+        show_command(ui, command, interactions);
+    }
+}
+
+fn show_command(ui: &mut egui::Ui, command: &Command, interactions: &mut Interactions) {
+    match command {
+        Command::Label(text) => {
+            ui.label(text);
+        }
+        Command::Button { id, text } => {
+            if ui.button(text).clicked() {
+                interactions.clicked.insert(id.clone());
+            }
+        }
+        Command::Checkbox { id, text, value } => {
+            let mut value = *value;
+            if ui.checkbox(&mut value, text).changed() {
+                interactions.changed.insert(id.clone(), Value::Bool(value));
+            }
+        }
+        Command::Slider { id, value, min, max } => {
+            let mut value = *value;
+            if ui.add(egui::Slider::new(&mut value, *min..=*max)).changed() {
+                interactions.changed.insert(id.clone(), Value::Float(value));
+            }
+        }
+        Command::TextEdit { id, text } => {
+            let mut text = text.clone();
+            if ui.add(egui::TextEdit::singleline(&mut text).id_source(id)).changed() {
+                interactions.changed.insert(id.clone(), Value::Text(text));
+            }
+        }
+        Command::Window { title, children } => show_window(ui.ctx(), title, children, interactions),
+        Command::Horizontal(children) => {
+            ui.horizontal(|ui| show_commands(ui, children, interactions));
+        }
+        Command::Vertical(children) => {
+            ui.vertical(|ui| show_commands(ui, children, interactions));
+        }
+    }
+}
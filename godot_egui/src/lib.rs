@@ -1,11 +1,12 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
 use egui::epaint::ImageDelta;
 use egui::{Event, FullOutput};
 use gdnative::api::{
-    GlobalConstants, ImageTexture, InputEventMouseButton, InputEventMouseMotion, ShaderMaterial, VisualServer,
+    CanvasItem, GlobalConstants, ImageTexture, InputEventMouseButton, InputEventMouseMotion, ShaderMaterial,
+    Theme, VisualServer,
 };
 
 #[cfg(feature = "theme_support")]
@@ -17,9 +18,51 @@ use gdnative::prelude::*;
 /// Contains conversion tables between Godot and egui input constants (keys, mouse buttons)
 pub(crate) mod enum_conversions;
 
+/// Routes input between overlapping `GodotEgui` nodes
+pub(crate) mod coordinator;
+
 /// Some helper functions and traits for godot-egui
 pub mod egui_helpers;
 pub mod ext;
+/// Hands the keyboard focus over between egui and Godot's focus system
+pub(crate) mod focus;
+/// Fonts loaded from the files and resources set in the Godot editor
+pub mod fonts;
+/// Conversion of Godot themes into egui styles
+pub mod godot_theme;
+/// Places native Godot controls inside the egui layout
+pub mod hosted_controls;
+/// Loads and caches textures by their resource path
+pub mod image_cache;
+/// An immediate-mode API for building UIs from GDScript
+pub mod immediate;
+/// Navigation of egui widgets with a gamepad
+pub mod navigation;
+/// Saves egui's memory between runs of the game
+#[cfg(feature = "persistence")]
+pub(crate) mod persistence;
+/// Draws egui windows and popups outside of the GodotEgui node
+pub(crate) mod popup_overlay;
+/// Resources shared by several GodotEgui nodes
+pub mod resource_group;
+/// Options and handles for the textures drawn by godot-egui
+pub mod textures;
+/// `.eguitheme` files as Godot resources
+#[cfg(feature = "theme_support")]
+pub mod theme_resource;
+/// Named themes that can be switched between at runtime
+pub(crate) mod theme_variants;
+
+use coordinator::Route;
+use focus::FocusBridge;
+use fonts::FontLibrary;
+use hosted_controls::NativeControlHost;
+use image_cache::{CachedImage, ImageCache};
+use navigation::{Navigation, NavigationActions};
+use popup_overlay::PopupOverlay;
+use resource_group::ResourceGroup;
+use textures::{GodotTextureHandle, RegisteredTextureInfo, TextureOptions, TextureRegistry};
+use theme_variants::ThemeTransition;
 
 /// Converts an egui color into a godot color
 pub fn egui2color(color: egui::Color32) -> Color {
@@ -36,16 +79,18 @@ pub fn color2egui(color: Color) -> egui::Color32 {
     // egui::Color32::from(egui::Rgba::from_rgba_premultiplied(c.r, c.g, c.b, c.a))
 }
 
-/// Converts a godot `Rid` into an `egui::TextureId`
-pub fn rid_to_egui_texture_id(x: Rid) -> egui::TextureId {
-    // Safety: see `Rid::to_u64`
-    unsafe { egui::TextureId::User(std::mem::transmute::<gdnative::sys::godot_rid, usize>(*x.sys()) as u64) }
+/// Converts a godot `Rid` into an `egui::TextureId`, or `None` if the `Rid` is empty. The `Rid` must belong to a
+/// resource that is alive. Prefer using the id of the `GodotTextureHandle` returned by
+/// `GodotEgui::register_godot_texture`.
+pub fn rid_to_egui_texture_id(x: Rid) -> Option<egui::TextureId> {
+    textures::rid_to_texture_id(x)
 }
 #[derive(ToVariant)]
 enum GodotEguiInputMode {
     None = 0,
     Input = 1,
     GuiInput = 2,
+    UnhandledInput = 3,
 }
 
 impl FromVariant for GodotEguiInputMode {
@@ -54,9 +99,11 @@ impl FromVariant for GodotEguiInputMode {
             0 => Ok(GodotEguiInputMode::None),
             1 => Ok(GodotEguiInputMode::Input),
             2 => Ok(GodotEguiInputMode::GuiInput),
-            _ => {
-                Err(FromVariantError::UnknownEnumVariant { variant: "i64".to_owned(), expected: &["0", "1", "2"] })
-            }
+            3 => Ok(GodotEguiInputMode::UnhandledInput),
+            _ => Err(FromVariantError::UnknownEnumVariant {
+                variant: "i64".to_owned(),
+                expected: &["0", "1", "2", "3"],
+            }),
         }
     }
 }
@@ -65,16 +112,32 @@ impl Export for GodotEguiInputMode {
     type Hint = gdnative::export::hint::IntHint<u32>;
 
     fn export_info(_hint: Option<Self::Hint>) -> ExportInfo {
-        Self::Hint::Enum(EnumHint::new(vec!["None".to_owned(), "Input".to_owned(), "GuiInput".to_owned()]))
-            .export_info()
+        Self::Hint::Enum(EnumHint::new(vec![
+            "None".to_owned(),
+            "Input".to_owned(),
+            "GuiInput".to_owned(),
+            "UnhandledInput".to_owned(),
+        ]))
+        .export_info()
     }
 }
 
+/// The id of the `egui::Area` that `GodotEgui::update` draws into when it doesn't use a `CentralPanel`.
+const CONTENT_AREA: &str = "godot_egui_content";
+
 /// Stores a canvas item, used by the visual server
 struct VisualServerMesh {
     canvas_item: Rid,
 }
 
+/// An opacity animation started by `GodotEgui::fade_to`
+struct Fade {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
 /// Core type to draw egui-based controls in Godot.
 /// The `update` or `update_ctx` methods can be used to draw a new frame.
 #[derive(NativeClass)]
@@ -83,24 +146,89 @@ struct VisualServerMesh {
 pub struct GodotEgui {
     pub egui_ctx: egui::Context,
     meshes: Vec<VisualServerMesh>,
+    /// The textures created by egui.
     textures: HashMap<egui::TextureId, Ref<Texture>>,
+    /// The Godot textures registered by the user, shared with every `GodotTextureHandle`.
+    user_textures: Rc<RefCell<TextureRegistry>>,
+    /// Textures loaded by resource path with `ctx_image`.
+    image_cache: ImageCache,
+    /// The name of the group of nodes this node shares its textures, image cache, font definitions and themes
+    /// with. The font atlas is not shared. Nodes with an empty name don't share their resources. See the
+    /// `resource_group` module.
+    #[property]
+    resource_group: String,
+    shared_resources: Option<ResourceGroup>,
+    /// The version of the shared fonts that was last applied to the context.
+    shared_fonts_generation: u64,
+    /// The fonts set on this node, which the `font_library` is applied to.
+    font_definitions: egui::FontDefinitions,
+    font_library: FontLibrary,
+    /// The number of frames an image from the image cache may go unused before it is evicted. Nodes in a
+    /// `resource_group` use the setting of the first node that joins it.
+    #[property(default = 300)]
+    image_cache_max_unused_frames: u32,
+    /// When enabled, the image cache loads images over several frames using a `ResourceInteractiveLoader` and
+    /// shows a placeholder in the meantime, instead of blocking until they are loaded. Nodes in a
+    /// `resource_group` use the setting of the first node that joins it.
+    #[property(default = false)]
+    image_cache_interactive_loading: bool,
+    /// Sampling options set for individual textures. Textures without an entry use their current flags, or the
+    /// default from `disable_texture_filtering` for textures created by egui.
+    texture_options: HashMap<egui::TextureId, TextureOptions>,
+    /// The native Godot controls placed inside the egui layout.
+    control_host: NativeControlHost,
     raw_input: Rc<RefCell<egui::RawInput>>,
     mouse_was_captured: bool,
     cursor_icon: egui::CursorIcon,
 
     shader_material: Option<Ref<ShaderMaterial, Shared>>,
+    /// A copy of `shader_material` that expands the single channel font atlas textures.
+    font_material: Option<Ref<ShaderMaterial, Shared>>,
+    /// The egui textures that contain a font atlas, stored as `Image::FORMAT_L8`.
+    font_textures: HashSet<egui::TextureId>,
+    /// The registered textures used by the meshes that were painted last.
+    drawn_textures: HashSet<egui::TextureId>,
     /// This flag will force a UI to redraw every frame.
     /// This can be used for when the UI's backend events are always changing.
     #[property(default = false)]
     reactive_update: bool,
     #[property]
     input_mode: GodotEguiInputMode,
+    /// When enabled with the `GuiInput` input mode, Tab and Shift+Tab move the focus between the egui widgets
+    /// and the other Godot controls, and Godot's focus follows egui's.
+    #[property(default = true)]
+    integrate_godot_focus: bool,
+    focus: FocusBridge,
+
+    /// When enabled, gamepad events that match the navigation actions move the focus between egui widgets,
+    /// adjust sliders and activate the focused widget. The right stick scrolls.
+    #[property(default = false)]
+    gamepad_navigation: bool,
+    /// The `InputMap` actions used by `gamepad_navigation`. An empty name disables the action.
+    #[property]
+    navigation_up_action: String,
+    #[property]
+    navigation_down_action: String,
+    #[property]
+    navigation_left_action: String,
+    #[property]
+    navigation_right_action: String,
+    #[property]
+    navigation_accept_action: String,
+    #[property]
+    navigation_cancel_action: String,
+    /// How far the right stick must be tilted before it scrolls.
+    #[property(default = 0.2)]
+    navigation_scroll_deadzone: f32,
+    navigation: Navigation,
 
     /// The amount of scrolled pixels per mouse wheel event
     #[property]
     scroll_speed: f32,
 
     /// When enabled, no texture filtering will be performed. Useful for a pixel-art style.
+    /// This is the default for textures created by egui, and can be overridden per texture with
+    /// `set_texture_options`.
     #[property]
     disable_texture_filtering: bool,
     /// Pixels per point controls the render scale of the objects in egui.
@@ -108,9 +236,80 @@ pub struct GodotEgui {
     /// The maximum side length egui should try to allocate for the font texture.
     #[property(default = 2048)]
     max_texture_side_length: u32,
+    /// The opacity egui is drawn with. This is combined with the `modulate` of this node and its parents.
+    opacity: f32,
+    /// When enabled, egui will not receive any input while it is faded out (or fading out) to zero opacity.
+    #[property(default = false)]
+    disable_input_when_faded: bool,
+    /// The opacity animation that is currently running, if any.
+    fade: Option<Fade>,
+    /// The modulate color that was last sent to the shader.
+    current_modulate: Color,
+    /// When enabled, the space used by egui is reported as the minimum size of this node, so it can be laid out
+    /// by Godot containers like any other `Control`.
+    #[property(default = false)]
+    shrink_to_content: bool,
+    /// The minimum size that was last reported to Godot, in pixels.
+    content_min_size: Vector2,
+    /// When enabled with the `GuiInput` input mode, mouse events only stop at this node where egui draws a
+    /// window, area or panel. Events over empty space reach the controls below and the rest of the game.
+    #[property(default = false)]
+    pass_through_empty_space: bool,
+    /// Whether the last point checked by `has_point` was over egui content.
+    last_point_hit: Cell<bool>,
+    /// When enabled with the `Input` or `UnhandledInput` input modes, this node shares its input with the other
+    /// coordinated `GodotEgui` nodes. Pointer events only reach the topmost node drawing egui content under the
+    /// pointer, and key events only reach the node that was clicked last.
+    #[property(default = false)]
+    coordinated_input: bool,
+    /// When enabled, egui windows, popups and tooltips can extend past the rect of this node and are drawn above
+    /// the rest of the UI. The main UI should then be drawn with `update`, or with areas that stay inside the node,
+    /// since panels fill the whole viewport.
+    #[property(default = false)]
+    popups_escape_bounds: bool,
+    popup_overlay: Option<PopupOverlay>,
+    /// Fonts added to egui's fonts, or to the fonts of the theme, in `_ready`. See the `fonts` module for the
+    /// accepted entries.
+    custom_fonts: VariantArray,
+    /// When enabled, the Godot `Theme` of this node or its nearest parent with a theme is converted into the egui
+    /// style in `_ready`. See the `godot_theme` module.
+    #[property(default = false)]
+    use_godot_theme: bool,
+    /// Themes that can be switched to with `set_active_theme`, by name. See the `theme_variants` module.
+    themes: Dictionary,
+    /// The name of the theme in `themes` that replaced the theme of this node. Empty if none did. Applying the
+    /// theme of the node again, e.g. when its file is reloaded, clears it.
+    active_theme: String,
+    /// When enabled, the `dark` or `light` theme is activated when the OS switches between dark and light mode, if
+    /// it reports it. A theme activated with `set_active_theme`, e.g. a high contrast theme, is kept until then.
+    /// # Note
+    /// Godot 3 doesn't report the dark mode of the OS, so this has no effect there and a warning is logged when the
+    /// node enters the tree. It only works on engines that provide `OS.is_dark_mode`.
+    #[property(default = false)]
+    follow_os_dark_mode: bool,
+    /// The dark mode the OS reported when it was last checked.
+    os_dark_mode: Option<bool>,
+    theme_transition: Option<ThemeTransition>,
     /// The theme resource that this GodotEgui control will use.
     #[cfg(feature = "theme_support")]
     theme_path: String,
+    /// A `GodotEguiTheme` resource, used instead of `theme_path` when it is set.
+    #[cfg(feature = "theme_support")]
+    theme_resource: Option<Ref<Resource>>,
+    /// How often, in seconds, the theme file is checked for changes and reloaded. 0 disables reloading.
+    #[cfg(feature = "theme_support")]
+    theme_reload_interval: f64,
+    #[cfg(feature = "theme_support")]
+    theme_reload_elapsed: f64,
+    /// The modified time of the theme file when it was last loaded.
+    #[cfg(feature = "theme_support")]
+    theme_modified_time: i64,
+    /// The file egui's memory is saved to, e.g. `user://egui_memory.ron`. Nothing is saved if it is empty.
+    #[cfg(feature = "persistence")]
+    memory_path: String,
+    /// Application state saved along with egui's memory, serialized with `ron`.
+    #[cfg(feature = "persistence")]
+    app_state: HashMap<String, String>,
 }
 
 #[gdnative::derive::methods]
@@ -121,10 +320,68 @@ impl GodotEgui {
         builder
             .property::<String>("EguiTheme")
             .with_getter(move |egui: &GodotEgui, _| egui.theme_path.clone())
-            .with_setter(move |egui: &mut GodotEgui, _, new_val| egui.theme_path = new_val)
+            .with_setter(move |egui: &mut GodotEgui, owner, new_val| {
+                let changed = egui.theme_path != new_val;
+                egui.theme_path = new_val;
+                // The theme is loaded in `_ready` when the node enters the tree.
+                if changed && owner.is_inside_tree() {
+                    egui.apply_theme(owner, true);
+                }
+            })
             .with_default("".to_owned())
             .with_hint(StringHint::File(EnumHint::new(vec!["*.ron".to_owned(), "*.eguitheme".to_owned()])))
             .done();
+        #[cfg(feature = "theme_support")]
+        builder
+            .property::<f64>("theme_reload_interval")
+            .with_getter(move |egui: &GodotEgui, _| egui.theme_reload_interval)
+            .with_setter(move |egui: &mut GodotEgui, _, new_val| egui.theme_reload_interval = new_val)
+            .with_default(0.0)
+            .done();
+        #[cfg(feature = "theme_support")]
+        builder
+            .property::<Option<Ref<Resource>>>("theme_resource")
+            .with_getter(move |egui: &GodotEgui, _| egui.theme_resource.clone())
+            .with_setter(move |egui: &mut GodotEgui, owner, new_val| {
+                egui.theme_resource = new_val;
+                if owner.is_inside_tree() {
+                    egui.apply_theme(owner, true);
+                }
+            })
+            .with_default(None)
+            .done();
+        builder
+            .property::<Dictionary>("themes")
+            .with_getter(move |egui: &GodotEgui, _| egui.themes.new_ref())
+            .with_setter(move |egui: &mut GodotEgui, _, new_val| egui.themes = new_val)
+            .with_default(Dictionary::new_shared())
+            .done();
+        builder
+            .property::<String>("active_theme")
+            .with_getter(move |egui: &GodotEgui, _| egui.active_theme.clone())
+            .with_setter(move |egui: &mut GodotEgui, owner, new_val| {
+                // The active theme is applied in `_ready` when the node enters the tree.
+                if owner.is_inside_tree() && !new_val.is_empty() {
+                    egui.set_active_theme(owner, new_val, 0.0);
+                } else {
+                    egui.active_theme = new_val;
+                }
+            })
+            .with_default("".to_owned())
+            .done();
+        builder
+            .property::<VariantArray>("custom_fonts")
+            .with_getter(move |egui: &GodotEgui, _| egui.custom_fonts.new_ref())
+            .with_setter(move |egui: &mut GodotEgui, _, new_val| egui.custom_fonts = new_val)
+            .with_default(VariantArray::new_shared())
+            .done();
+        #[cfg(feature = "persistence")]
+        builder
+            .property::<String>("memory_path")
+            .with_getter(move |egui: &GodotEgui, _| egui.memory_path.clone())
+            .with_setter(move |egui: &mut GodotEgui, _, new_val| egui.memory_path = new_val)
+            .with_default("".to_owned())
+            .done();
         builder
             .property::<f64>("pixels_per_point")
             .with_getter(move |egui: &GodotEgui, _| egui.pixels_per_point)
@@ -132,25 +389,100 @@ impl GodotEgui {
             .with_default(1.0)
             .with_hint(FloatHint::Range(RangeHint::new(0.01, 16.0).with_step(0.01)))
             .done();
+        builder
+            .property::<f32>("opacity")
+            .with_getter(move |egui: &GodotEgui, _| egui.opacity)
+            // Setting the property stops a running fade, like `set_opacity`.
+            .with_setter(move |egui: &mut GodotEgui, owner, new_value: f32| egui.set_opacity(owner, new_value))
+            .with_default(1.0)
+            .with_hint(FloatHint::Range(RangeHint::new(0.0, 1.0).with_step(0.01)))
+            .done();
+        // Emitted when an animation started with `fade_to` reaches its target opacity.
+        builder.signal("fade_finished").done();
+        // Emitted with the name of the theme when `set_active_theme` switches to it.
+        builder.signal("theme_changed").with_param("name", VariantType::GodotString).done();
+        // Emitted with the path and the error when the theme file can't be loaded. The last theme that loaded is kept.
+        #[cfg(feature = "theme_support")]
+        builder
+            .signal("theme_load_failed")
+            .with_param("path", VariantType::GodotString)
+            .with_param("error", VariantType::GodotString)
+            .done();
     }
     /// Constructs a new egui node
     pub fn new(_owner: TRef<Control>) -> GodotEgui {
+        let user_textures = Rc::new(RefCell::new(TextureRegistry::default()));
         GodotEgui {
             egui_ctx: Default::default(),
             meshes: vec![],
             textures: HashMap::new(),
+            user_textures: user_textures.clone(),
+            image_cache: ImageCache::new(user_textures),
+            resource_group: String::new(),
+            shared_resources: None,
+            shared_fonts_generation: 0,
+            font_definitions: Default::default(),
+            font_library: Default::default(),
+            image_cache_max_unused_frames: 300,
+            image_cache_interactive_loading: false,
+            texture_options: HashMap::new(),
+            control_host: NativeControlHost::default(),
             raw_input: Rc::new(RefCell::new(egui::RawInput::default())),
             mouse_was_captured: false,
             cursor_icon: egui::CursorIcon::Default,
             reactive_update: false,
             input_mode: GodotEguiInputMode::None,
+            integrate_godot_focus: true,
+            focus: FocusBridge::default(),
+            gamepad_navigation: false,
+            navigation_up_action: "ui_up".to_owned(),
+            navigation_down_action: "ui_down".to_owned(),
+            navigation_left_action: "ui_left".to_owned(),
+            navigation_right_action: "ui_right".to_owned(),
+            navigation_accept_action: "ui_accept".to_owned(),
+            navigation_cancel_action: "ui_cancel".to_owned(),
+            navigation_scroll_deadzone: 0.2,
+            navigation: Navigation::default(),
             shader_material: None,
+            font_material: None,
+            font_textures: HashSet::new(),
+            drawn_textures: HashSet::new(),
             scroll_speed: 20.0,
             disable_texture_filtering: false,
             pixels_per_point: 1f64,
             max_texture_side_length: 2048,
+            opacity: 1.0,
+            disable_input_when_faded: false,
+            shrink_to_content: false,
+            pass_through_empty_space: false,
+            last_point_hit: Cell::new(false),
+            coordinated_input: false,
+            popups_escape_bounds: false,
+            popup_overlay: None,
+            content_min_size: Vector2::ZERO,
+            fade: None,
+            current_modulate: Color::from_rgba(1.0, 1.0, 1.0, 1.0),
+            custom_fonts: VariantArray::new_shared(),
+            use_godot_theme: false,
+            themes: Dictionary::new_shared(),
+            active_theme: "".to_owned(),
+            follow_os_dark_mode: false,
+            os_dark_mode: None,
+            theme_transition: None,
             #[cfg(feature = "theme_support")]
             theme_path: "".to_owned(),
+            #[cfg(feature = "theme_support")]
+            theme_resource: None,
+            #[cfg(feature = "theme_support")]
+            theme_reload_interval: 0.0,
+            #[cfg(feature = "theme_support")]
+            theme_reload_elapsed: 0.0,
+            #[cfg(feature = "theme_support")]
+            theme_modified_time: 0,
+            #[cfg(feature = "persistence")]
+            memory_path: "".to_owned(),
+            #[cfg(feature = "persistence")]
+            app_state: HashMap::new(),
         }
     }
 
@@ -164,12 +496,137 @@ impl GodotEgui {
             godot_error!("pixels per point must be greater than 0");
         }
     }
-    /// Updates egui from the `_input` callback
+    /// Sets the opacity egui is drawn with, cancelling any running fade.
+    #[export]
+    pub fn set_opacity(&mut self, _owner: TRef<Control>, opacity: f32) {
+        self.fade = None;
+        self.opacity = opacity.clamp(0.0, 1.0);
+    }
+
+    /// Animates the opacity of the whole UI from its current value to `alpha` over `duration` seconds.
+    /// `fade_finished` is emitted once the target opacity is reached.
+    #[export]
+    pub fn fade_to(&mut self, owner: TRef<Control>, alpha: f32, duration: f32) {
+        let alpha = alpha.clamp(0.0, 1.0);
+        if duration <= 0.0 {
+            self.fade = None;
+            self.opacity = alpha;
+            owner.emit_signal("fade_finished", &[]);
+        } else {
+            self.fade = Some(Fade { from: self.opacity, to: alpha, duration, elapsed: 0.0 });
+        }
+    }
+
+    /// Returns true while egui is faded out and `disable_input_when_faded` is set.
+    #[export]
+    pub fn is_input_disabled_by_fade(&self, _owner: TRef<Control>) -> bool {
+        let fading_out = self.fade.as_ref().map_or(self.opacity <= 0.0, |fade| fade.to <= 0.0);
+        self.disable_input_when_faded && fading_out
+    }
+
+    /// Advances the opacity animation and keeps the shader in sync with the `modulate` of the node.
+    #[export]
+    fn _process(&mut self, owner: TRef<Control>, delta: f64) {
+        if let Some(fade) = &mut self.fade {
+            fade.elapsed += delta as f32;
+            let t = (fade.elapsed / fade.duration).min(1.0);
+            self.opacity = fade.from + (fade.to - fade.from) * t;
+            if t >= 1.0 {
+                self.fade = None;
+                owner.emit_signal("fade_finished", &[]);
+            }
+        }
+        if let Some(transition) = &mut self.theme_transition {
+            let (visuals, finished) = transition.advance(delta as f32);
+            self.egui_ctx.set_style(egui::Style { visuals, ..(*self.egui_ctx.style()).clone() });
+            self.egui_ctx.request_repaint();
+            if finished {
+                self.theme_transition = None;
+            }
+        }
+        self.update_modulate(&owner);
+        #[cfg(feature = "theme_support")]
+        self.poll_theme_file(owner, delta);
+    }
+
+    #[export]
+    fn _notification(&mut self, owner: TRef<Control>, what: i64) {
+        #[cfg(feature = "persistence")]
+        if what == Node::NOTIFICATION_WM_QUIT_REQUEST {
+            self.save_memory();
+        }
+        // The dark mode may have changed while the window was in the background.
+        if what == Node::NOTIFICATION_WM_FOCUS_IN && self.follow_os_dark_mode {
+            self.follow_os_theme(owner, 0.25);
+        }
+        if !self.integrates_godot_focus() {
+            return;
+        }
+        match what {
+            Control::NOTIFICATION_FOCUS_ENTER => self.focus.on_focus_enter(&self.egui_ctx),
+            Control::NOTIFICATION_FOCUS_EXIT => self.focus.on_focus_exit(&self.egui_ctx),
+            _ => return,
+        }
+        self.egui_ctx.request_repaint();
+    }
+
+    /// Whether the focus is handed over between egui and Godot, see `integrate_godot_focus`.
+    fn integrates_godot_focus(&self) -> bool {
+        self.integrate_godot_focus && matches!(self.input_mode, GodotEguiInputMode::GuiInput)
+    }
+
+    /// The combined `modulate` of this node and its `CanvasItem` parents, with `opacity` applied.
+    fn effective_modulate(&self, owner: &Control) -> Color {
+        let multiply = |a: Color, b: Color| Color::from_rgba(a.r * b.r, a.g * b.g, a.b * b.b, a.a * b.a);
+        let mut modulate = multiply(owner.modulate(), owner.self_modulate());
+        let mut parent = owner.get_parent();
+        while let Some(node) = parent {
+            match unsafe { node.assume_safe() }.cast::<CanvasItem>() {
+                Some(canvas_item) => {
+                    modulate = multiply(modulate, canvas_item.modulate());
+                    parent = canvas_item.get_parent();
+                }
+                None => break,
+            }
+        }
+        modulate.a *= self.opacity;
+        modulate
+    }
+
+    /// Sends the effective modulate to the egui shader when it has changed.
+    fn update_modulate(&mut self, owner: &Control) {
+        let modulate = self.effective_modulate(owner);
+        if modulate != self.current_modulate {
+            self.current_modulate = modulate;
+            for material in self.shader_material.iter().chain(&self.font_material) {
+                unsafe { material.assume_safe() }.set_shader_param("egui_modulate", modulate);
+            }
+        }
+    }
 
     /// Run when this node is added to the scene tree. Runs some initialization logic, like registering any
-    /// custom fonts defined as properties
+    /// custom fonts defined as properties in `custom_fonts`
     #[export]
     fn _ready(&mut self, owner: TRef<Control>) {
+        // Join the resource group first, so textures registered from here on are shared.
+        if !self.resource_group.is_empty() {
+            let group = ResourceGroup::get_or_create(&self.resource_group);
+            self.user_textures = group.texture_registry();
+            self.image_cache = group.image_cache();
+            let max_unused_frames = self.image_cache_max_unused_frames as u64;
+            if !group.configure_image_cache(max_unused_frames, self.image_cache_interactive_loading) {
+                godot_warn!(
+                    "the image cache settings of {} differ from the first node of the resource group {}, whose \
+                     settings are used",
+                    owner.name(),
+                    self.resource_group
+                );
+            }
+            self.shared_resources = Some(group);
+        }
+        // Restored before the fonts and style are set up, since replacing the memory discards pending fonts.
+        #[cfg(feature = "persistence")]
+        self.load_memory();
         match self.input_mode {
             GodotEguiInputMode::None => {
                 godot_print!("GodotEgui is not accepting input");
@@ -186,12 +643,25 @@ impl GodotEgui {
             }
             GodotEguiInputMode::GuiInput => {
                 godot_print!("GodotEgui is accepting GUI input");
-                // Ignore input
-                owner.set_process_input(false);
+                // Ignore input, except for the pointer motion used to find popups outside of the node
+                owner.set_process_input(self.popups_escape_bounds);
                 // Accept GUI Input
                 owner.set_mouse_filter(Control::MOUSE_FILTER_PASS);
                 owner.set_focus_mode(Control::FOCUS_ALL);
             }
+            GodotEguiInputMode::UnhandledInput => {
+                godot_print!("GodotEgui is accepting unhandled input");
+                owner.set_process_input(false);
+                owner.set_process_unhandled_input(true);
+                owner.set_process_unhandled_key_input(true);
+                // Ignore GUI input, so events that no control consumed reach `_unhandled_input`
+                owner.set_mouse_filter(Control::MOUSE_FILTER_IGNORE);
+                owner.set_focus_mode(Control::FOCUS_NONE);
+            }
+        }
+        if !matches!(self.input_mode, GodotEguiInputMode::UnhandledInput) {
+            owner.set_process_unhandled_input(false);
+            owner.set_process_unhandled_key_input(false);
         }
         // This decision is so that we do not have to recompile when testing the shaders.
         // TODO: Make this a build feature flag.
@@ -208,6 +678,18 @@ impl GodotEgui {
             shader_material.set_shader(shader);
             Some(shader_material.into_shared())
         };
+        // Font atlases are uploaded with a single channel, so they are drawn with a copy of the material that
+        // expands them back into a white texture with the coverage as alpha.
+        self.font_material = self.shader_material.as_ref().and_then(|material| {
+            let font_material = unsafe { material.assume_safe() }.duplicate(false)?;
+            let font_material = unsafe { font_material.assume_safe() }.cast::<ShaderMaterial>()?;
+            font_material.set_shader_param("font_atlas", true);
+            Some(font_material.claim())
+        });
+        self.current_modulate = self.effective_modulate(&owner);
+        for material in self.shader_material.iter().chain(&self.font_material) {
+            unsafe { material.assume_safe() }.set_shader_param("egui_modulate", self.current_modulate);
+        }
 
         // Run a single dummy frame to ensure the fonts are created, otherwise egui panics
         self.egui_ctx.begin_frame(egui::RawInput {
@@ -219,33 +701,243 @@ impl GodotEgui {
         for (texture_id, delta) in textures_delta.set {
             self.set_texture(texture_id, &delta)
         }
+        self.apply_theme(owner, false);
+        // The theme picked in the inspector replaces the theme of the node, and the OS only decides the theme if
+        // none was picked.
+        if !self.active_theme.is_empty() {
+            let name = std::mem::take(&mut self.active_theme);
+            self.set_active_theme(owner, name, 0.0);
+        }
+        if self.follow_os_dark_mode {
+            if theme_variants::engine_reports_dark_mode() {
+                self.follow_os_theme(owner, 0.0);
+            } else {
+                godot_warn!(
+                    "follow_os_dark_mode has no effect, since this version of Godot doesn't report the dark mode of \
+                     the OS"
+                );
+            }
+        }
+    }
+
+    /// Applies the `theme_resource` or the theme at `theme_path`, the `custom_fonts` and, with `use_godot_theme`,
+    /// the Godot theme, replacing the active theme. When `reload` is set, the theme file is read again, and the
+    /// current style and fonts are kept if it can't be loaded.
+    fn apply_theme(&mut self, owner: TRef<Control>, reload: bool) {
+        let mut font_definitions: Option<egui::FontDefinitions> = None;
+        #[cfg(not(feature = "theme_support"))]
+        let _ = reload;
         #[cfg(feature = "theme_support")]
-        // We do not check if the themepath is empty.
-        if !self.theme_path.is_empty() {
-            let file = File::new();
-            if file.file_exists(self.theme_path.as_str()) {
-                match file.open(self.theme_path.as_str(), File::READ) {
-                    Ok(_) => {
-                        let file_data = file.get_as_text();
-                        match ron::from_str::<egui_theme::EguiTheme>(file_data.to_string().as_str()) {
-                            Ok(theme) => {
-                                let (style, font_definitions) = theme.extract();
-                                self.egui_ctx.set_style(style);
-                                self.egui_ctx.set_fonts(font_definitions);
-                            }
-                            Err(err) => {
-                                godot_error!("Theme could not be deserialized due to: {:#?}", err);
-                            }
-                        }
-                    }
-                    Err(error) => {
-                        godot_error!("{}", error);
-                    }
+        if let Some(resource) = &self.theme_resource {
+            match theme_resource::theme_from_resource(resource) {
+                Some((style, theme_fonts)) => {
+                    self.egui_ctx.set_style(style);
+                    font_definitions = Some(theme_fonts);
                 }
-            } else {
-                godot_error!("file {} does not exist", &self.theme_path)
+                None => godot_error!("theme_resource must be a GodotEguiTheme"),
+            }
+        } else if !self.theme_path.is_empty() {
+            match self.load_theme_file(owner, reload) {
+                Some((style, theme_fonts)) => {
+                    self.egui_ctx.set_style(style);
+                    font_definitions = Some(theme_fonts);
+                }
+                None if reload => return,
+                None => {}
+            }
+        }
+        if !self.custom_fonts.is_empty() {
+            let mut fonts = font_definitions.unwrap_or_default();
+            fonts::add_custom_fonts(&mut fonts, &self.custom_fonts);
+            font_definitions = Some(fonts);
+        }
+        if let Some(font_definitions) = font_definitions {
+            self.set_font_definitions(font_definitions);
+        }
+        if self.use_godot_theme {
+            match godot_theme::find_theme(&owner) {
+                Some(theme) => self.apply_godot_theme(owner, theme),
+                None => {
+                    godot_warn!("use_godot_theme is enabled, but neither GodotEgui nor its parents have a theme")
+                }
+            }
+        }
+        // The fonts of the library are added again on top of the new fonts.
+        self.font_library.invalidate();
+        self.egui_ctx.request_repaint();
+        self.active_theme.clear();
+        self.theme_transition = None;
+    }
+
+    /// Switches to the theme called `name` in `themes`, or to the built-in `light` or `dark` theme, replacing the
+    /// style of this node. With a `transition` longer than 0 seconds, the colors are animated into the new theme.
+    /// Returns false and keeps the current theme if it can't be found or loaded.
+    #[export]
+    pub fn set_active_theme(&mut self, owner: TRef<Control>, name: String, transition: f32) -> bool {
+        let current = self.egui_ctx.style();
+        let (style, font_definitions) = match theme_variants::theme_by_name(&self.themes, &name, &current) {
+            Ok(theme) => theme,
+            Err(error) => {
+                godot_error!("{}", error);
+                return false;
             }
+        };
+        if transition > 0.0 {
+            let to = style.visuals.clone();
+            self.egui_ctx.set_style(egui::Style { visuals: current.visuals.clone(), ..style });
+            self.theme_transition = Some(ThemeTransition::new(current.visuals.clone(), to, transition));
+        } else {
+            self.egui_ctx.set_style(style);
+            self.theme_transition = None;
+        }
+        if let Some(font_definitions) = font_definitions {
+            self.set_font_definitions(font_definitions);
+            self.font_library.invalidate();
         }
+        self.active_theme = name.clone();
+        owner.emit_signal("theme_changed", &[name.to_variant()]);
+        self.egui_ctx.request_repaint();
+        true
+    }
+
+    /// Activates the `dark` or `light` theme when the dark mode reported by the OS changes. The first reported
+    /// mode is only applied if no theme is active yet, so a theme picked by the user is kept until the OS switches.
+    fn follow_os_theme(&mut self, owner: TRef<Control>, transition: f32) {
+        let dark = match theme_variants::os_dark_mode() {
+            Some(dark) => dark,
+            None => return,
+        };
+        match self.os_dark_mode.replace(dark) {
+            Some(previous) if previous == dark => return,
+            None if !self.active_theme.is_empty() => return,
+            _ => {}
+        }
+        let name = if dark { theme_variants::DARK } else { theme_variants::LIGHT };
+        self.set_active_theme(owner, name.to_owned(), transition);
+    }
+
+    /// Loads the theme at `theme_path`, from the cache of the resource group unless `reload` is set. Errors are
+    /// logged and reported with the `theme_load_failed` signal.
+    #[cfg(feature = "theme_support")]
+    fn load_theme_file(
+        &mut self, owner: TRef<Control>, reload: bool,
+    ) -> Option<(egui::Style, egui::FontDefinitions)> {
+        let path = self.theme_path.clone();
+        self.theme_modified_time = File::new().get_modified_time(path.as_str());
+        let theme = match &self.shared_resources {
+            Some(group) if !reload => group.theme(&path, || theme_resource::load_egui_theme(&path)),
+            Some(group) => theme_resource::load_egui_theme(&path).map(|theme| {
+                group.set_theme(&path, theme.clone());
+                theme
+            }),
+            None => theme_resource::load_egui_theme(&path),
+        };
+        match theme {
+            Ok(theme) => Some(theme),
+            Err(error) => {
+                godot_error!("{}", error);
+                owner.emit_signal("theme_load_failed", &[path.to_variant(), error.to_variant()]);
+                None
+            }
+        }
+    }
+
+    /// Reloads the theme when its file was modified, checking every `theme_reload_interval` seconds.
+    #[cfg(feature = "theme_support")]
+    fn poll_theme_file(&mut self, owner: TRef<Control>, delta: f64) {
+        if self.theme_reload_interval <= 0.0 || self.theme_path.is_empty() {
+            return;
+        }
+        self.theme_reload_elapsed += delta;
+        if self.theme_reload_elapsed < self.theme_reload_interval {
+            return;
+        }
+        self.theme_reload_elapsed = 0.0;
+        if File::new().get_modified_time(self.theme_path.as_str()) != self.theme_modified_time {
+            self.apply_theme(owner, true);
+        }
+    }
+
+    /// Converts a Godot `Theme` into the egui style, on top of the current one. The default font of the theme is
+    /// added to the fonts.
+    #[export]
+    pub fn apply_godot_theme(&mut self, _owner: TRef<Control>, theme: Ref<Theme>) {
+        let theme = unsafe { theme.assume_safe() };
+        let style = godot_theme::style_from_godot_theme(&theme, &self.egui_ctx.style());
+        self.egui_ctx.set_style(style);
+        if let Some(font_definitions) = godot_theme::fonts_from_godot_theme(&theme, self.font_definitions.clone())
+        {
+            self.set_font_definitions(font_definitions);
+        }
+    }
+
+    /// Creates a Godot `Theme` from the current egui style, so native Godot controls can match egui.
+    #[export]
+    pub fn create_godot_theme(&self, _owner: TRef<Control>) -> Ref<Theme, Unique> {
+        godot_theme::style_to_godot_theme(&self.egui_ctx.style())
+    }
+
+    /// Converts a Godot `Theme` and saves the result as an `.eguitheme` file at `path`. Requires the
+    /// `theme_support` feature.
+    #[export]
+    pub fn save_godot_theme_as_egui_theme(&mut self, _owner: TRef<Control>, theme: Ref<Theme>, path: String) {
+        #[cfg(feature = "theme_support")]
+        {
+            let theme = unsafe { theme.assume_safe() };
+            let style = godot_theme::style_from_godot_theme(&theme, &self.egui_ctx.style());
+            let font_definitions = godot_theme::fonts_from_godot_theme(&theme, self.font_definitions.clone())
+                .unwrap_or_else(|| self.font_definitions.clone());
+            if let Err(error) = godot_theme::save_egui_theme(&path, style, font_definitions) {
+                godot_error!("{}", error);
+            }
+        }
+        #[cfg(not(feature = "theme_support"))]
+        {
+            let _ = (theme, path);
+            godot_error!("saving themes requires the theme_support feature of godot_egui");
+        }
+    }
+
+    /// Sets the fonts of this node, or of every node in its resource group.
+    fn set_font_definitions(&mut self, font_definitions: egui::FontDefinitions) {
+        match &self.shared_resources {
+            Some(group) => group.set_fonts(font_definitions.clone()),
+            None => self.egui_ctx.set_fonts(font_definitions.clone()),
+        }
+        self.font_definitions = font_definitions;
+    }
+
+    /// Returns the fonts and fallback chains of this node, which can be changed from code.
+    pub fn font_library(&mut self) -> &mut FontLibrary {
+        &mut self.font_library
+    }
+
+    /// Adds the font file at `path` to the font library. Lazy fonts are only read when a character is missing from
+    /// the fonts before them in a fallback chain.
+    #[export]
+    pub fn add_font(&mut self, _owner: TRef<Control>, name: String, path: String, lazy: bool) {
+        let source = fonts::FontSource::new(path);
+        self.font_library.add(&name, if lazy { source.lazy() } else { source });
+    }
+
+    /// Scales and moves the glyphs of a font, e.g. to match the baseline of the other fonts of a fallback chain.
+    #[export]
+    pub fn set_font_tweak(
+        &mut self, _owner: TRef<Control>, name: String, scale: f32, y_offset_factor: f32, y_offset: f32,
+    ) {
+        self.font_library.set_tweak(&name, egui::FontTweak { scale, y_offset_factor, y_offset });
+    }
+
+    /// Sets the fonts of a family (`proportional`, `monospace` or a custom name) in the order they are tried.
+    #[export]
+    pub fn set_fallback_chain(&mut self, _owner: TRef<Control>, family: String, fonts: StringArray) {
+        let fonts = fonts.read().iter().map(|name| name.to_string()).collect();
+        self.font_library.set_chain(fonts::parse_family(&family), fonts);
+    }
+
+    /// Returns the group this node shares its resources with, if `resource_group` is set.
+    pub fn shared_resources(&self) -> Option<ResourceGroup> {
+        self.shared_resources.clone()
     }
 
     /// Is used to indicate if the mouse was captured during the previous frame.
@@ -256,13 +948,138 @@ impl GodotEgui {
 
     #[export]
     pub fn _input(&mut self, owner: TRef<Control>, event: Ref<InputEvent>) {
+        if matches!(self.input_mode, GodotEguiInputMode::GuiInput) {
+            // With `popups_escape_bounds`, egui follows the pointer outside of this node, so it knows when the
+            // pointer is over a popup and the overlay should receive the mouse events.
+            if unsafe { event.assume_safe() }.cast::<InputEventMouseMotion>().is_some() {
+                self.handle_godot_input(owner, event, false);
+            }
+            return;
+        }
+        let route = self.route_input(owner, &event);
+        if route == Route::Skip {
+            return;
+        }
         self.handle_godot_input(owner, event, false);
-        if self.mouse_was_captured(owner) {
+        if route == Route::DeliverAndCapture || self.mouse_was_captured(owner) {
             // Set the input as handled by the viewport if the gui believes that is has been captured.
             unsafe { owner.get_viewport().expect("Viewport").assume_safe().set_input_as_handled() };
         }
     }
 
+    /// Updates egui from the `_unhandled_input` callback. Key events are handled by `_unhandled_key_input`.
+    #[export]
+    pub fn _unhandled_input(&mut self, owner: TRef<Control>, event: Ref<InputEvent>) {
+        if unsafe { event.assume_safe() }.cast::<InputEventKey>().is_some() {
+            return;
+        }
+        self.handle_unhandled_input(owner, event);
+    }
+
+    /// Updates egui from the `_unhandled_key_input` callback
+    #[export]
+    pub fn _unhandled_key_input(&mut self, owner: TRef<Control>, event: Ref<InputEventKey>) {
+        self.handle_unhandled_input(owner, event.upcast());
+    }
+
+    fn handle_unhandled_input(&mut self, owner: TRef<Control>, event: Ref<InputEvent>) {
+        if !matches!(self.input_mode, GodotEguiInputMode::UnhandledInput) {
+            return;
+        }
+        let route = self.route_input(owner, &event);
+        if route == Route::Skip {
+            return;
+        }
+        let is_key = unsafe { event.assume_safe() }.cast::<InputEventKey>().is_some();
+        // Unhandled events use viewport coordinates, like the events of `_input`.
+        self.handle_godot_input(owner, event, false);
+        let captured = if is_key { self.egui_ctx.wants_keyboard_input() } else { self.mouse_was_captured(owner) };
+        if route == Route::DeliverAndCapture || captured {
+            unsafe { owner.get_viewport().expect("Viewport").assume_safe().set_input_as_handled() };
+        }
+    }
+
+    /// Asks the coordinator whether this node receives an event, see `coordinated_input`.
+    fn route_input(&self, owner: TRef<Control>, event: &Ref<InputEvent>) -> Route {
+        if self.is_coordinated() {
+            coordinator::route(owner, unsafe { event.assume_safe() })
+        } else {
+            Route::Deliver
+        }
+    }
+
+    fn is_coordinated(&self) -> bool {
+        self.coordinated_input
+            && matches!(self.input_mode, GodotEguiInputMode::Input | GodotEguiInputMode::UnhandledInput)
+    }
+
+    #[export]
+    fn _enter_tree(&mut self, owner: TRef<Control>) {
+        if self.is_coordinated() {
+            coordinator::register(owner, &self.egui_ctx, &self.raw_input);
+        }
+    }
+
+    #[export]
+    fn _exit_tree(&mut self, owner: TRef<Control>) {
+        coordinator::unregister(owner);
+        #[cfg(feature = "persistence")]
+        self.save_memory();
+    }
+
+    /// Restores egui's memory and the application state from `memory_path`, keeping the current options.
+    #[cfg(feature = "persistence")]
+    fn load_memory(&mut self) {
+        if self.memory_path.is_empty() {
+            return;
+        }
+        if let Some(persistence::LoadedState { mut memory, app_state }) = persistence::load(&self.memory_path) {
+            let mut current = self.egui_ctx.memory();
+            memory.options = current.options.clone();
+            *current = memory;
+            self.app_state = app_state;
+        }
+    }
+
+    /// Saves egui's memory and the application state to `memory_path`. This happens automatically when the node
+    /// exits the tree and when the game is asked to quit.
+    #[cfg(feature = "persistence")]
+    pub fn save_memory(&self) {
+        if self.memory_path.is_empty() {
+            return;
+        }
+        persistence::save(&self.memory_path, &self.egui_ctx.memory(), &self.app_state);
+    }
+
+    /// Stores a value that is saved along with egui's memory, see `memory_path`.
+    #[cfg(feature = "persistence")]
+    pub fn set_persisted_state<T: serde::Serialize>(&mut self, key: &str, value: &T) {
+        match ron::to_string(value) {
+            Ok(value) => {
+                self.app_state.insert(key.to_owned(), value);
+            }
+            Err(err) => godot_error!("failed to serialize the persisted state {}: {}", key, err),
+        }
+    }
+
+    /// Returns a value stored with `set_persisted_state`, possibly during a previous run of the game. Returns
+    /// `None` if there is no value for the key, or if it can't be deserialized as `T`.
+    #[cfg(feature = "persistence")]
+    pub fn persisted_state<T: serde::de::DeserializeOwned>(&self, key: &str) -> Option<T> {
+        ron::from_str(self.app_state.get(key)?).ok()
+    }
+
+    /// Receives the mouse events over the windows and popups drawn outside of this node, see
+    /// `popups_escape_bounds`. The overlay stops them from reaching the controls below in every input mode, but
+    /// they are only forwarded to egui in the modes where they wouldn't reach it otherwise. In `Input` mode,
+    /// `_input` has already passed them on.
+    #[export]
+    fn _on_popup_overlay_gui_input(&mut self, owner: TRef<Control>, event: Ref<InputEvent>) {
+        if matches!(self.input_mode, GodotEguiInputMode::GuiInput | GodotEguiInputMode::UnhandledInput) {
+            self.handle_godot_input(owner, event, false);
+        }
+    }
+
     /// Updates egui from the `_gui_input` callback
     #[export]
     pub fn _gui_input(&mut self, owner: TRef<Control>, event: Ref<InputEvent>) {
@@ -278,6 +1095,10 @@ impl GodotEgui {
     /// # Note: If you are calling this manually, self.input_mode *MUST* be set to GodotEguiInputMode::None
     #[export]
     pub fn handle_godot_input(&mut self, owner: TRef<Control>, event: Ref<InputEvent>, is_gui_input: bool) {
+        if self.is_input_disabled_by_fade(owner) {
+            self.mouse_was_captured = false;
+            return;
+        }
         let event = unsafe { event.assume_safe() };
         let mut raw_input = self.raw_input.borrow_mut();
         let pixels_per_point = self.egui_ctx.pixels_per_point();
@@ -298,11 +1119,37 @@ impl GodotEgui {
             egui::Pos2 { x: transformed_pos.x * points_per_pixel, y: transformed_pos.y * points_per_pixel }
         };
 
+        if self.gamepad_navigation {
+            let actions = NavigationActions {
+                up: &self.navigation_up_action,
+                down: &self.navigation_down_action,
+                left: &self.navigation_left_action,
+                right: &self.navigation_right_action,
+                accept: &self.navigation_accept_action,
+                cancel: &self.navigation_cancel_action,
+            };
+            if self.navigation.handle_event(event, &actions, &mut raw_input.events) {
+                if is_gui_input {
+                    owner.accept_event();
+                }
+                return;
+            }
+        }
+
         if let Some(motion_ev) = event.cast::<InputEventMouseMotion>() {
-            raw_input.events.push(egui::Event::PointerMoved(mouse_pos_to_egui(motion_ev.position())))
+            let pos = mouse_pos_to_egui(motion_ev.position());
+            // Hosted controls receive the mouse events over their area instead of egui.
+            if self.control_host.contains(pos) {
+                raw_input.events.push(egui::Event::PointerGone)
+            } else {
+                raw_input.events.push(egui::Event::PointerMoved(pos))
+            }
         }
 
         if let Some(button_ev) = event.cast::<InputEventMouseButton>() {
+            if self.control_host.contains(mouse_pos_to_egui(button_ev.position())) {
+                return;
+            }
             if let Some(button) = enum_conversions::mouse_button_index_to_egui(button_ev.button_index()) {
                 raw_input.events.push(egui::Event::PointerButton {
                     pos: mouse_pos_to_egui(button_ev.position()),
@@ -328,6 +1175,22 @@ impl GodotEgui {
         }
 
         if let Some(key_ev) = event.cast::<InputEventKey>() {
+            // Hosted controls with the keyboard focus receive the key events instead of egui.
+            if self.control_host.has_focus(&owner) {
+                return;
+            }
+            if is_gui_input
+                && self.integrates_godot_focus()
+                && key_ev.is_pressed()
+                && key_ev.scancode() == GlobalConstants::KEY_TAB
+                && self.focus.on_tab(
+                    &owner,
+                    &self.egui_ctx,
+                    (key_ev.get_scancode_with_modifiers() & GlobalConstants::KEY_MASK_SHIFT) != 0,
+                )
+            {
+                return;
+            }
             if let Some(key) = enum_conversions::scancode_to_egui(key_ev.scancode()) {
                 let mods = key_ev.get_scancode_with_modifiers();
                 let modifiers = egui::Modifiers {
@@ -349,14 +1212,141 @@ impl GodotEgui {
         }
     }
 
-    pub fn register_godot_texture(&mut self, texture: Ref<Texture>) {
+    /// Registers a godot texture so it can be drawn by egui using the `texture_id` of the returned handle.
+    /// The texture stays registered until every handle to it is dropped or `unregister_godot_texture` is called.
+    /// Returns `None` for a texture without a rid, e.g. an `ImageTexture` without an image or an `AtlasTexture`
+    /// without an atlas.
+    pub fn register_godot_texture(&mut self, texture: Ref<Texture>) -> Option<GodotTextureHandle> {
+        let handle = GodotTextureHandle::new(&self.user_textures, texture);
+        if handle.is_none() {
+            godot_error!("the texture can't be registered, since it has no rid");
+        }
+        handle
+    }
+
+    /// Registers a godot texture and sets its sampling options. See `set_texture_options`.
+    pub fn register_godot_texture_with_options(
+        &mut self, texture: Ref<Texture>, options: TextureOptions,
+    ) -> Option<GodotTextureHandle> {
+        let handle = self.register_godot_texture(texture)?;
+        self.set_texture_options(handle.texture_id(), options);
+        Some(handle)
+    }
+
+    /// Unregisters a godot texture even if there are handles that still reference it. Returns true if the
+    /// texture was registered.
+    #[export]
+    pub fn unregister_godot_texture(&mut self, _owner: TRef<Control>, texture: Ref<Texture>) -> bool {
+        let texture_id = match rid_to_egui_texture_id(unsafe { texture.assume_safe() }.get_rid()) {
+            Some(texture_id) => texture_id,
+            None => return false,
+        };
+        self.texture_options.remove(&texture_id);
+        self.user_textures.borrow_mut().remove(texture_id).is_some()
+    }
+
+    /// The number of godot textures that are currently registered. Useful to track down leaked handles.
+    #[export]
+    pub fn registered_texture_count(&self, _owner: TRef<Control>) -> i64 {
+        self.user_textures.borrow().len() as i64
+    }
+
+    /// Returns every godot texture that is currently registered.
+    #[export]
+    pub fn registered_textures(&self, _owner: TRef<Control>) -> VariantArray {
+        let textures = VariantArray::new();
+        for info in self.user_textures.borrow().info() {
+            textures.push(info.texture);
+        }
+        textures.into_shared()
+    }
+
+    /// Marks a registered godot texture as changing every frame, so egui keeps repainting while it is drawn.
+    /// `AnimatedTexture`s and `ViewportTexture`s don't need this, they are repainted when their frame or size
+    /// changes. Returns false if the texture is not registered.
+    #[export]
+    pub fn set_godot_texture_animated(
+        &mut self, _owner: TRef<Control>, texture: Ref<Texture>, animated: bool,
+    ) -> bool {
+        match rid_to_egui_texture_id(unsafe { texture.assume_safe() }.get_rid()) {
+            Some(texture_id) => self.user_textures.borrow_mut().set_animated(texture_id, animated),
+            None => false,
+        }
+    }
+
+    /// Lists every registered godot texture along with the number of handles that reference it.
+    pub fn registered_texture_info(&self) -> Vec<RegisteredTextureInfo> {
+        self.user_textures.borrow().info()
+    }
+
+    /// Returns the image at the given resource path (e.g. `res://icons/sword.png`) from the image cache, loading it
+    /// the first time it is requested. Use `image_cache` to access images from inside `update_ctx`.
+    pub fn ctx_image(&self, path: &str) -> CachedImage {
+        self.image_cache.image(path)
+    }
+
+    /// Returns the cache used by `ctx_image`. Cloning it is cheap and every clone refers to the same images, so a
+    /// clone can be moved into the closures passed to `update` and `update_ctx`.
+    pub fn image_cache(&self) -> ImageCache {
+        self.image_cache.clone()
+    }
+
+    /// Returns the host used to place native Godot controls inside the egui layout. Like the image cache, it can
+    /// be cloned into the closures passed to `update` and `update_ctx`.
+    pub fn native_control_host(&self) -> NativeControlHost {
+        self.control_host.clone()
+    }
+
+    /// Looks up a texture that egui can draw, either created by egui or registered by the user.
+    fn get_texture(&self, texture_id: &egui::TextureId) -> Option<Ref<Texture>> {
+        match texture_id {
+            egui::TextureId::Managed(_) => self.textures.get(texture_id).cloned(),
+            egui::TextureId::User(_) => self.user_textures.borrow().get(texture_id).cloned(),
+        }
+    }
+
+    /// Sets the sampling options of a registered texture or a texture created by egui. The options are kept
+    /// when egui recreates its textures.
+    /// # Note
+    /// The options are applied as flags on the Godot `Texture`, so any other node using the same texture
+    /// resource will be affected as well.
+    pub fn set_texture_options(&mut self, texture_id: egui::TextureId, options: TextureOptions) {
+        self.texture_options.insert(texture_id, options);
+        if let Some(texture) = self.get_texture(&texture_id) {
+            unsafe { texture.assume_safe() }.set_flags(options.to_flags());
+        }
+    }
+
+    /// Returns the sampling options of a texture drawn by egui, if it exists.
+    pub fn texture_options(&self, texture_id: egui::TextureId) -> Option<TextureOptions> {
+        self.texture_options.get(&texture_id).copied().or_else(|| {
+            self.get_texture(&texture_id)
+                .map(|texture| TextureOptions::from_flags(unsafe { texture.assume_safe() }.flags()))
+        })
+    }
+
+    /// Sets the sampling options of a registered godot texture from GDScript. See `set_texture_options` for more
+    /// details.
+    #[export]
+    pub fn set_godot_texture_options(
+        &mut self, _owner: TRef<Control>, texture: Ref<Texture>, filter: bool, mipmaps: bool, repeat: bool,
+    ) {
+        let filter = if filter { textures::TextureFilter::Linear } else { textures::TextureFilter::Nearest };
         let rid = unsafe { texture.assume_safe().get_rid() };
-        self.textures.insert(rid_to_egui_texture_id(rid), texture);
+        let texture_id = match rid_to_egui_texture_id(rid) {
+            Some(texture_id) => texture_id,
+            None => {
+                godot_error!("the options can't be set on a texture without a rid");
+                return;
+            }
+        };
+        self.set_texture_options(texture_id, TextureOptions { filter, mipmaps, repeat });
     }
 
     fn set_texture(&mut self, texture_id: egui::TextureId, delta: &egui::epaint::ImageDelta) {
-        let texture_flags =
-            if self.disable_texture_filtering { 0 } else { Texture::FLAG_FILTER | Texture::FLAG_MIPMAPS };
+        let default_options =
+            if self.disable_texture_filtering { TextureOptions::NEAREST } else { TextureOptions::LINEAR };
+        let texture_flags = self.texture_options.get(&texture_id).copied().unwrap_or(default_options).to_flags();
 
         let texture = &*self.textures.entry(texture_id).or_insert_with(|| {
             assert!(delta.pos.is_none(), "when creating a new texture, the delta must be the full texture");
@@ -366,25 +1356,39 @@ impl GodotEgui {
         let texture = unsafe { texture.assume_safe() };
         let texture = texture.cast::<ImageTexture>().expect("`ImageTexture` is subclass of `Texture`");
 
+        if let egui::ImageData::Font(_) = &delta.image {
+            self.font_textures.insert(texture_id);
+        }
         let delta_image = Self::image_from_delta(delta);
 
         if let Some(pos) = &delta.pos {
-            // partial update, blit the delta onto the texture at the correct position
-            let texture_image = texture.get_data().expect("this must exist");
-            let texture_image = unsafe { texture_image.assume_safe() };
-            // use the entire delta image
-            let blit_rect = Rect2 { position: Vector2::ZERO, size: delta_image.get_size() };
-            texture_image.blit_rect(delta_image, blit_rect, Vector2::new(pos[0] as _, pos[1] as _));
-            texture.set_data(texture_image);
+            // partial update, only upload the delta to the region of the texture that changed.
+            let vs = unsafe { VisualServer::godot_singleton() };
+            let (width, height) = (delta.image.width() as i64, delta.image.height() as i64);
+            unsafe {
+                vs.texture_set_data_partial(
+                    texture.get_rid(),
+                    delta_image,
+                    0,
+                    0,
+                    width,
+                    height,
+                    pos[0] as i64,
+                    pos[1] as i64,
+                    0,
+                    0,
+                );
+            }
         } else {
             // full update means size changed, so we need to recreate the texture using the new image
             texture.create_from_image(delta_image, texture_flags);
         };
     }
 
-    /// Create a Godot `Image` from an egui `ImageDelta`
+    /// Create a Godot `Image` from an egui `ImageDelta`. Font atlases only store their coverage, so they are
+    /// created as `Image::FORMAT_L8` instead of `Image::FORMAT_RGBA8`.
     fn image_from_delta(delta: &ImageDelta) -> Ref<Image, Unique> {
-        let pixels: ByteArray = match &delta.image {
+        let (format, pixels): (i64, ByteArray) = match &delta.image {
             egui::ImageData::Color(egui_image) => {
                 assert_eq!(
                     egui_image.width() * egui_image.height(),
@@ -392,7 +1396,7 @@ impl GodotEgui {
                     "Mismatch between texture size and texel count"
                 );
 
-                egui_image.pixels.iter().flat_map(|color| color.to_array()).collect()
+                (Image::FORMAT_RGBA8, egui_image.pixels.iter().flat_map(|color| color.to_array()).collect())
             }
             egui::ImageData::Font(egui_image) => {
                 assert_eq!(
@@ -402,18 +1406,14 @@ impl GodotEgui {
                 );
                 // I don't really know what this is for but it was
                 let gamma = 1.0 / 2.2;
-                egui_image.srgba_pixels(gamma).flat_map(|a| a.to_array()).collect()
+                // This matches `FontImage::srgba_pixels`, where every channel of the color is the same value.
+                let coverage = |coverage: &f32| (coverage.powf(gamma / 2.2) * 255.0 + 0.5) as u8;
+                (Image::FORMAT_L8, egui_image.pixels.iter().map(coverage).collect())
             }
         };
 
         let delta_image = Image::new();
-        delta_image.create_from_data(
-            delta.image.width() as _,
-            delta.image.height() as _,
-            false,
-            Image::FORMAT_RGBA8,
-            pixels,
-        );
+        delta_image.create_from_data(delta.image.width() as _, delta.image.height() as _, false, format, pixels);
         delta_image
     }
 
@@ -429,6 +1429,11 @@ impl GodotEgui {
             .as_ref()
             .map(|material| unsafe { material.assume_safe() }.get_rid())
             .expect("should be initialized");
+        let font_material_rid = self
+            .font_material
+            .as_ref()
+            .map(|material| unsafe { material.assume_safe() }.get_rid())
+            .unwrap_or(material_rid);
 
         let vs = unsafe { VisualServer::godot_singleton() };
 
@@ -463,10 +1468,38 @@ impl GodotEgui {
             "At this point, the number of canvas items should be the same as the number of egui meshes."
         );
 
+        self.drawn_textures.clear();
+
+        // With `popups_escape_bounds`, the meshes from the first one that is not clipped to the node onwards are drawn
+        // above the rest of the UI in the popup overlay.
+        let overlay_from = match &self.popup_overlay {
+            Some(_) if self.popups_escape_bounds => {
+                let size = owner.get_rect().size;
+                let node_rect = egui::Rect::from_min_size(
+                    egui::Pos2::ZERO,
+                    egui::vec2(size.x / pixels_per_point, size.y / pixels_per_point),
+                )
+                .expand(0.5);
+                clipped_meshes
+                    .iter()
+                    .position(|clipped| !node_rect.contains_rect(clipped.clip_rect))
+                    .unwrap_or(clipped_meshes.len())
+            }
+            _ => clipped_meshes.len(),
+        };
+
         // Paint the meshes
-        for (egui::ClippedPrimitive { clip_rect, primitive }, vs_mesh) in
-            clipped_meshes.into_iter().zip(self.meshes.iter_mut())
+        for (idx, (egui::ClippedPrimitive { clip_rect, primitive }, vs_mesh)) in
+            clipped_meshes.into_iter().zip(self.meshes.iter_mut()).enumerate()
         {
+            let parent = match &self.popup_overlay {
+                Some(overlay) if idx >= overlay_from => overlay.canvas_item(),
+                _ => owner.get_canvas_item(),
+            };
+            unsafe {
+                vs.canvas_item_set_parent(vs_mesh.canvas_item, parent);
+            }
+
             let mesh = if let egui::epaint::Primitive::Mesh(mesh) = primitive {
                 mesh
             } else {
@@ -481,12 +1514,18 @@ impl GodotEgui {
             }
             assert!(mesh.is_valid(), "mesh is invalid");
 
-            let texture_rid = self.textures.get(&mesh.texture_id);
-            if texture_rid.is_none() {
-                godot_print!("{:?} does not exist", &mesh.texture_id);
-                continue;
+            let texture_rid = match self.get_texture(&mesh.texture_id) {
+                Some(texture) => unsafe { texture.assume_safe() }.get_rid(),
+                None => {
+                    godot_print!("{:?} does not exist", &mesh.texture_id);
+                    continue;
+                }
+            };
+            if let egui::TextureId::User(_) = mesh.texture_id {
+                self.drawn_textures.insert(mesh.texture_id);
             }
-            let texture_rid = unsafe { texture_rid.unwrap().assume_safe().get_rid() };
+            let mesh_material_rid =
+                if self.font_textures.contains(&mesh.texture_id) { font_material_rid } else { material_rid };
 
             for mut mesh in mesh.split_to_u16() {
                 // First we need to get the indicies and map them to the i32 which godot understands.
@@ -510,7 +1549,7 @@ impl GodotEgui {
 
                 unsafe {
                     vs.canvas_item_clear(vs_mesh.canvas_item);
-                    vs.canvas_item_set_material(vs_mesh.canvas_item, material_rid);
+                    vs.canvas_item_set_material(vs_mesh.canvas_item, mesh_material_rid);
 
                     vs.canvas_item_add_triangle_array(
                         vs_mesh.canvas_item,
@@ -552,6 +1591,8 @@ impl GodotEgui {
             // Cleanup textures as required
             for &id in &egui_texture_deltas.free {
                 self.textures.remove(&id);
+                self.texture_options.remove(&id);
+                self.font_textures.remove(&id);
             }
         }
     }
@@ -594,12 +1635,62 @@ impl GodotEgui {
         // Ensure that the egui context fills the entire space of the node and is adjusted accordinglly.
         let size = owner.get_rect().size;
         let points_per_pixel = (1.0 / self.pixels_per_point) as f32;
-        raw_input.screen_rect = Some(egui::Rect::from_min_size(
-            Default::default(),
-            egui::Vec2::new(size.x * points_per_pixel, size.y * points_per_pixel),
-        ));
+        raw_input.screen_rect = Some(if self.popups_escape_bounds {
+            // egui may draw over the whole viewport, which is offset so the node stays at the origin.
+            let origin = owner.get_global_transform_with_canvas().origin;
+            let viewport_size = owner.get_viewport_rect().size;
+            egui::Rect::from_min_size(
+                egui::pos2(-origin.x * points_per_pixel, -origin.y * points_per_pixel),
+                egui::Vec2::new(viewport_size.x * points_per_pixel, viewport_size.y * points_per_pixel),
+            )
+        } else {
+            egui::Rect::from_min_size(
+                Default::default(),
+                egui::Vec2::new(size.x * points_per_pixel, size.y * points_per_pixel),
+            )
+        });
+
+        if self.font_library.is_dirty() {
+            let current = match &self.shared_resources {
+                Some(group) => group.fonts().unwrap_or_else(|| self.font_definitions.clone()),
+                None => self.font_definitions.clone(),
+            };
+            let font_definitions = self.font_library.build(current);
+            self.set_font_definitions(font_definitions);
+        }
+
+        // Apply the fonts shared by the resource group when they change.
+        if let Some(group) = &self.shared_resources {
+            if group.fonts_generation() != self.shared_fonts_generation {
+                self.shared_fonts_generation = group.fonts_generation();
+                if let Some(fonts) = group.fonts() {
+                    self.egui_ctx.set_fonts(fonts);
+                }
+            }
+        }
+
+        let focus_events = self.focus.take_events();
+        if !focus_events.is_empty() {
+            raw_input.events.extend(focus_events);
+            self.egui_ctx.request_repaint();
+        }
+        let focus_before = self.egui_ctx.memory().focus();
+        if self.gamepad_navigation {
+            let scroll_events =
+                self.navigation.scroll_events(&self.egui_ctx, self.scroll_speed, self.navigation_scroll_deadzone);
+            if !scroll_events.is_empty() {
+                raw_input.events.extend(scroll_events);
+                self.egui_ctx.request_repaint();
+            }
+        }
 
         self.egui_ctx.begin_frame(raw_input);
+        self.navigation.begin_frame(&self.egui_ctx);
+        // The image cache of a resource group is configured by the first node that joins it.
+        if self.shared_resources.is_none() {
+            self.image_cache
+                .configure(self.image_cache_max_unused_frames as u64, self.image_cache_interactive_loading);
+        }
 
         // This ensures that while not using `reactive_update` that the UI is redrawn each frame regardless of whether the output would
         // normally request a repaint.
@@ -618,6 +1709,26 @@ impl GodotEgui {
         // shouldn't be an issue.
         self.mouse_was_captured = self.egui_ctx.is_using_pointer();
 
+        // Images that finished loading replace their placeholder on the next frame.
+        if self.image_cache.end_frame() {
+            self.egui_ctx.request_repaint();
+        }
+        // Lazy fonts needed by this frame are loaded for the next one.
+        if self.font_library.request_missing(&self.egui_ctx, &shapes) {
+            self.egui_ctx.request_repaint();
+        }
+        // Animated and viewport textures are repainted when they change, so their size is kept up to date.
+        if self.user_textures.borrow_mut().poll_changes(&self.drawn_textures) {
+            self.egui_ctx.request_repaint();
+        }
+
+        self.update_content_min_size(owner);
+        self.update_popup_overlay(owner);
+        self.control_host.end_frame(owner, self.egui_ctx.pixels_per_point());
+        if self.integrates_godot_focus() {
+            self.focus.end_frame(owner, &self.egui_ctx, focus_before);
+        }
+
         // When we have a new cursor, we need to update the Godot side.
         if self.cursor_icon != platform_output.cursor_icon {
             self.cursor_icon = platform_output.cursor_icon;
@@ -633,18 +1744,120 @@ impl GodotEgui {
     /// Call this to draw a new frame using a closure taking an `egui::Ui` parameter. Prefer this over
     /// `update_ctx` if the `CentralPanel` is going to be used for convenience. Accepts an optional
     /// `egui::Frame` to draw the panel background
+    /// # Note
+    /// When `shrink_to_content`, `pass_through_empty_space` or `popups_escape_bounds` is enabled, an `egui::Area`
+    /// is used instead of the `CentralPanel`, since the panel always fills the whole screen.
     pub fn update(&mut self, owner: &Control, frame: Option<egui::Frame>, draw_fn: impl FnOnce(&mut egui::Ui)) {
+        let frame = frame.unwrap_or(egui::Frame {
+            inner_margin: egui::style::Margin::symmetric(10.0, 10.0),
+            fill: (egui::Color32::from_white_alpha(0)),
+            ..Default::default()
+        });
+        let fit_content = self.shrink_to_content || self.pass_through_empty_space || self.popups_escape_bounds;
+        // With `popups_escape_bounds` the screen covers the whole viewport, so the content is kept inside the node.
+        let node_rect = if self.popups_escape_bounds {
+            let size = owner.get_rect().size;
+            let points_per_pixel = (1.0 / self.pixels_per_point) as f32;
+            Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(size.x * points_per_pixel, size.y * points_per_pixel),
+            ))
+        } else {
+            None
+        };
         self.update_ctx(owner, |egui_ctx| {
             // Run user code
-            egui::CentralPanel::default()
-                .frame(frame.unwrap_or(egui::Frame {
-                    inner_margin: egui::style::Margin::symmetric(10.0, 10.0),
-                    fill: (egui::Color32::from_white_alpha(0)),
-                    ..Default::default()
-                }))
-                .show(egui_ctx, draw_fn);
+            if fit_content {
+                let content = egui::Area::new(CONTENT_AREA)
+                    .order(egui::Order::Background)
+                    .fixed_pos(egui::Pos2::ZERO)
+                    .show(egui_ctx, |ui| {
+                        if let Some(node_rect) = node_rect {
+                            ui.set_clip_rect(node_rect);
+                            ui.set_max_size(node_rect.size());
+                        }
+                        frame.show(ui, draw_fn)
+                    });
+                // Background areas aren't part of `used_rect`, so the content is measured separately.
+                egui_ctx.data().insert_temp(egui::Id::new(CONTENT_AREA), content.response.rect);
+            } else {
+                egui::CentralPanel::default().frame(frame).show(egui_ctx, draw_fn);
+            }
         })
     }
+
+    /// Tells Godot whether a point inside this node receives mouse events. When `pass_through_empty_space` is
+    /// enabled, only the points where egui draws something do.
+    /// # Note
+    /// A `CentralPanel` doesn't count as egui content, so draw with `update`, windows, areas or side panels when
+    /// using `pass_through_empty_space`.
+    #[export]
+    fn has_point(&self, owner: TRef<Control>, point: Vector2) -> bool {
+        let size = owner.get_size();
+        let inside = point.x >= 0.0 && point.y >= 0.0 && point.x < size.x && point.y < size.y;
+        if !inside || !self.pass_through_empty_space {
+            return inside;
+        }
+        let points_per_pixel = (1.0 / self.egui_ctx.pixels_per_point()) as f32;
+        let hit = coordinator::is_egui_content_at(
+            &self.egui_ctx,
+            egui::pos2(point.x * points_per_pixel, point.y * points_per_pixel),
+        );
+        // Godot checks the same point several times per event, so this only reacts to the pointer leaving egui.
+        if self.last_point_hit.replace(hit) && !hit {
+            // egui no longer receives the mouse events here, so it should stop hovering its widgets.
+            self.raw_input.borrow_mut().events.push(egui::Event::PointerGone);
+        }
+        hit
+    }
+
+    /// Creates the popup overlay when `popups_escape_bounds` is first enabled, and keeps it in sync with the node.
+    fn update_popup_overlay(&mut self, owner: &Control) {
+        if self.popups_escape_bounds && self.popup_overlay.is_none() {
+            let owner = unsafe { owner.assume_shared().assume_safe() };
+            self.popup_overlay = Some(PopupOverlay::new(owner, "_on_popup_overlay_gui_input"));
+        }
+        if let Some(overlay) = &self.popup_overlay {
+            // The overlay receives the mouse events while the pointer is over a window or popup, or while egui is
+            // dragging something that was picked up inside the node.
+            let over_popup = self.egui_ctx.pointer_hover_pos().map_or(false, |pos| {
+                self.egui_ctx.layer_id_at(pos).map_or(false, |layer| layer.order != egui::Order::Background)
+            });
+            let wants_pointer = self.popups_escape_bounds && (over_popup || self.egui_ctx.is_using_pointer());
+            overlay.update(owner, wants_pointer);
+        }
+    }
+
+    /// Measures the space used by egui and reports it as the minimum size of the node when it changes.
+    fn update_content_min_size(&mut self, owner: &Control) {
+        let content_id = egui::Id::new(CONTENT_AREA);
+        let content = self.egui_ctx.data().get_temp::<egui::Rect>(content_id);
+        self.egui_ctx.data().remove::<egui::Rect>(content_id);
+        let min_size = if self.shrink_to_content {
+            let used =
+                content.map_or(self.egui_ctx.used_rect(), |content| self.egui_ctx.used_rect().union(content));
+            let pixels_per_point = self.pixels_per_point as f32;
+            Vector2::new(
+                (used.max.x.max(0.0) * pixels_per_point).ceil(),
+                (used.max.y.max(0.0) * pixels_per_point).ceil(),
+            )
+        } else {
+            Vector2::ZERO
+        };
+        if min_size != self.content_min_size {
+            self.content_min_size = min_size;
+            owner.minimum_size_changed();
+        }
+    }
+
+    /// Reports the space used by egui to Godot containers when `shrink_to_content` is enabled.
+    /// # Note
+    /// Only the space used by areas, windows and panels is measured. A `CentralPanel` always fills the whole
+    /// node, so draw with `update` or an `egui::Area` when using this mode.
+    #[export]
+    fn _get_minimum_size(&self, _owner: TRef<Control>) -> Vector2 {
+        self.content_min_size
+    }
 }
 
 // This `Drop` is required to ensure that the VisualServerMesh RIDs are properly freed when GodotEgui is freed.
@@ -656,6 +1869,9 @@ impl Drop for GodotEgui {
                 vs.free_rid(mesh.canvas_item);
             }
         }
+        if let Some(overlay) = &self.popup_overlay {
+            overlay.free_canvas_item();
+        }
     }
 }
 
@@ -665,6 +1881,14 @@ impl Drop for GodotEgui {
 /// in `gdnative` errors.
 pub fn register_classes(handle: InitHandle) {
     handle.add_class::<GodotEgui>();
+    handle.add_class::<immediate::GodotEguiImmediate>();
+    #[cfg(feature = "theme_support")]
+    {
+        handle.add_class::<theme_resource::GodotEguiTheme>();
+        // The loader and saver are also used by the editor, so they are always tool classes.
+        handle.add_tool_class::<theme_resource::GodotEguiThemeLoader>();
+        handle.add_tool_class::<theme_resource::GodotEguiThemeSaver>();
+    }
 }
 
 /// Helper method that registers all GodotEgui `NativeClass` objects as tool scripts. This should **only** be
@@ -673,4 +1897,11 @@ pub fn register_classes(handle: InitHandle) {
 /// `gdnative` errors.
 pub fn register_classes_as_tool(handle: InitHandle) {
     handle.add_tool_class::<GodotEgui>();
+    handle.add_tool_class::<immediate::GodotEguiImmediate>();
+    #[cfg(feature = "theme_support")]
+    {
+        handle.add_tool_class::<theme_resource::GodotEguiTheme>();
+        handle.add_tool_class::<theme_resource::GodotEguiThemeLoader>();
+        handle.add_tool_class::<theme_resource::GodotEguiThemeSaver>();
+    }
 }
@@ -0,0 +1,168 @@
+//! Navigation of egui widgets with a gamepad, driven by the actions of the Godot `InputMap`.
+//!
+//! Navigation actions are translated into the keys egui already understands:
+//! - The up and down actions move the focus to the previous and next interactive widget (Shift+Tab and Tab).
+//! - The left and right actions adjust the focused slider or drag value (the arrow keys).
+//! - The accept action activates the focused widget (Enter) and the cancel action clears the focus (Escape).
+//!
+//! The left stick moves the focus like the up and down actions, and the right stick scrolls the scroll area that
+//! contains the focused widget. egui only scrolls the area under the pointer, so widgets inside a scroll area need
+//! to call `NavigationExt::follow_focus` on their response for this to work.
+use std::collections::HashSet;
+
+use gdnative::api::{GlobalConstants, InputEventJoypadMotion, InputEventMouse};
+use gdnative::prelude::*;
+
+/// A stick must be tilted past this value before it moves the focus.
+const STICK_THRESHOLD: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum NavigationCommand {
+    Previous,
+    Next,
+    Left,
+    Right,
+    Activate,
+    Cancel,
+}
+
+impl NavigationCommand {
+    fn key(self) -> (egui::Key, egui::Modifiers) {
+        let shift = egui::Modifiers { shift: true, ..Default::default() };
+        match self {
+            NavigationCommand::Previous => (egui::Key::Tab, shift),
+            NavigationCommand::Next => (egui::Key::Tab, Default::default()),
+            NavigationCommand::Left => (egui::Key::ArrowLeft, Default::default()),
+            NavigationCommand::Right => (egui::Key::ArrowRight, Default::default()),
+            NavigationCommand::Activate => (egui::Key::Enter, Default::default()),
+            NavigationCommand::Cancel => (egui::Key::Escape, Default::default()),
+        }
+    }
+}
+
+/// The names of the `InputMap` actions used to navigate egui.
+pub(crate) struct NavigationActions<'a> {
+    pub up: &'a str,
+    pub down: &'a str,
+    pub left: &'a str,
+    pub right: &'a str,
+    pub accept: &'a str,
+    pub cancel: &'a str,
+}
+
+#[derive(Default)]
+pub(crate) struct Navigation {
+    /// The commands whose action is currently held, so holding a button or stick only moves the focus once.
+    held: HashSet<NavigationCommand>,
+    /// The joypad that was last used to navigate, used to read the right stick.
+    device: i64,
+}
+
+impl Navigation {
+    /// Translates a gamepad event into egui events. Returns true if the event was used for navigation.
+    /// Keyboard events are left alone, since egui handles the keyboard on its own.
+    pub(crate) fn handle_event(
+        &mut self, event: TRef<InputEvent>, actions: &NavigationActions, events: &mut Vec<egui::Event>,
+    ) -> bool {
+        if event.cast::<InputEventKey>().is_some() || event.cast::<InputEventMouse>().is_some() {
+            return false;
+        }
+        self.device = event.device();
+
+        let mut handled = false;
+        let commands = [
+            (actions.up, NavigationCommand::Previous),
+            (actions.down, NavigationCommand::Next),
+            (actions.left, NavigationCommand::Left),
+            (actions.right, NavigationCommand::Right),
+            (actions.accept, NavigationCommand::Activate),
+            (actions.cancel, NavigationCommand::Cancel),
+        ];
+        for &(action, command) in commands.iter() {
+            if action.is_empty() || !event.is_action(action, false) {
+                continue;
+            }
+            handled = true;
+            self.set_held(command, event.is_action_pressed(action, false, false), events);
+        }
+
+        // The left stick moves the focus even when it is not part of the actions.
+        if let Some(motion) = event.cast::<InputEventJoypadMotion>() {
+            let value = motion.axis_value() as f32;
+            let (negative, positive) = match motion.axis() {
+                GlobalConstants::JOY_AXIS_0 => (NavigationCommand::Left, NavigationCommand::Right),
+                GlobalConstants::JOY_AXIS_1 => (NavigationCommand::Previous, NavigationCommand::Next),
+                _ => return handled,
+            };
+            self.set_held(negative, value <= -STICK_THRESHOLD, events);
+            self.set_held(positive, value >= STICK_THRESHOLD, events);
+            handled = true;
+        }
+        handled
+    }
+
+    /// Called at the start of every egui frame, before the widgets are shown.
+    pub(crate) fn begin_frame(&self, ctx: &egui::Context) {
+        // The focused widget stores its rect again if it calls `follow_focus`.
+        ctx.data().remove::<egui::Rect>(focus_rect_id());
+    }
+
+    /// Returns the scroll events for the right stick of the joypad that was last used. The pointer is moved over
+    /// the focused widget first, so the scroll area that contains it is the one that scrolls.
+    pub(crate) fn scroll_events(&self, ctx: &egui::Context, scroll_speed: f32, deadzone: f32) -> Vec<egui::Event> {
+        let input = Input::godot_singleton();
+        let axis = |axis| {
+            let value = input.get_joy_axis(self.device, axis) as f32;
+            if value.abs() < deadzone {
+                0.0
+            } else {
+                value
+            }
+        };
+        let delta = egui::vec2(axis(GlobalConstants::JOY_AXIS_2), axis(GlobalConstants::JOY_AXIS_3));
+        if delta == egui::Vec2::ZERO {
+            return Vec::new();
+        }
+
+        let mut events = Vec::new();
+        if ctx.memory().focus().is_some() {
+            if let Some(rect) = ctx.data().get_temp::<egui::Rect>(focus_rect_id()) {
+                events.push(egui::Event::PointerMoved(rect.center()));
+            }
+        }
+        events.push(egui::Event::Scroll(-delta * scroll_speed));
+        events
+    }
+
+    fn set_held(&mut self, command: NavigationCommand, pressed: bool, events: &mut Vec<egui::Event>) {
+        let (key, modifiers) = command.key();
+        if pressed && self.held.insert(command) {
+            events.push(egui::Event::Key { key, pressed: true, modifiers });
+        } else if !pressed && self.held.remove(&command) {
+            events.push(egui::Event::Key { key, pressed: false, modifiers });
+        }
+    }
+}
+
+fn focus_rect_id() -> egui::Id {
+    egui::Id::new("godot_egui_navigation_focus_rect")
+}
+
+/// Helpers for widgets that are navigated with a gamepad.
+pub trait NavigationExt {
+    /// Scrolls the widget into view when it gains the focus, and lets the right stick scroll the scroll area that
+    /// contains it while it is focused.
+    fn follow_focus(self) -> Self;
+}
+
+impl NavigationExt for egui::Response {
+    fn follow_focus(self) -> Self {
+        if self.gained_focus() {
+            self.scroll_to_me(None);
+        }
+        if self.has_focus() {
+            self.ctx.data().insert_temp(focus_rect_id(), self.rect);
+        }
+        self
+    }
+}
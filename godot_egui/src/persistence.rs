@@ -0,0 +1,103 @@
+//! Saves the `egui::Memory` of a `GodotEgui` node to a file, so window positions, collapsing headers and scroll
+//! offsets survive a restart of the game. Requires the `persistence` feature.
+//!
+//! Values stored with `egui::Context::data().insert_persisted` are saved along with the memory. Application state
+//! that doesn't belong to a widget can be stored with `GodotEgui::set_persisted_state`.
+//!
+//! The options of the memory, which include the style, are not restored, since they are set up by the node itself.
+use std::collections::HashMap;
+
+use gdnative::api::{Directory, File};
+use gdnative::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The version of the saved files. It must be incremented whenever the saved format changes, e.g. when egui is
+/// updated. Files saved with another version are ignored.
+const FORMAT_VERSION: u32 = 1;
+
+/// Read first, so files with another version are ignored without trying to read the rest.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+#[derive(Serialize)]
+struct SavedState<'a> {
+    version: u32,
+    memory: &'a egui::Memory,
+    app_state: &'a HashMap<String, String>,
+}
+
+/// The state read from a file.
+#[derive(Deserialize)]
+pub(crate) struct LoadedState {
+    pub memory: egui::Memory,
+    #[serde(default)]
+    pub app_state: HashMap<String, String>,
+}
+
+/// Reads the state saved at `path`. Returns `None` if there is no file yet, or if it can't be used.
+pub(crate) fn load(path: &str) -> Option<LoadedState> {
+    let file = File::new();
+    if !file.file_exists(path) {
+        return None;
+    }
+    if let Err(err) = file.open(path, File::READ) {
+        godot_warn!("failed to open the egui memory at {}: {}", path, err);
+        return None;
+    }
+    let data = file.get_as_text().to_string();
+    file.close();
+
+    match ron::from_str::<Header>(&data) {
+        Ok(header) if header.version == FORMAT_VERSION => {}
+        Ok(header) => {
+            godot_warn!(
+                "ignoring the egui memory at {}, saved with version {} instead of {}",
+                path,
+                header.version,
+                FORMAT_VERSION
+            );
+            return None;
+        }
+        Err(err) => {
+            godot_warn!("ignoring the egui memory at {}: {}", path, err);
+            return None;
+        }
+    }
+    match ron::from_str::<LoadedState>(&data) {
+        Ok(state) => Some(state),
+        Err(err) => {
+            godot_warn!("ignoring the egui memory at {}: {}", path, err);
+            None
+        }
+    }
+}
+
+/// Writes the memory and application state to `path`, creating its directory if needed.
+pub(crate) fn save(path: &str, memory: &egui::Memory, app_state: &HashMap<String, String>) {
+    let state = SavedState { version: FORMAT_VERSION, memory, app_state };
+    let data = match ron::ser::to_string_pretty(&state, ron::ser::PrettyConfig::default()) {
+        Ok(data) => data,
+        Err(err) => {
+            godot_error!("failed to serialize the egui memory: {}", err);
+            return;
+        }
+    };
+
+    let directory = GodotString::from(path).get_base_dir();
+    let dir = Directory::new();
+    if !dir.dir_exists(directory.clone()) {
+        if let Err(err) = dir.make_dir_recursive(directory) {
+            godot_error!("failed to create the directory of the egui memory at {}: {}", path, err);
+            return;
+        }
+    }
+    let file = File::new();
+    if let Err(err) = file.open(path, File::WRITE) {
+        godot_error!("failed to save the egui memory at {}: {}", path, err);
+        return;
+    }
+    file.store_string(data);
+    file.close();
+}
@@ -0,0 +1,80 @@
+//! An overlay covering the whole viewport, used by `popups_escape_bounds` so egui windows, popups and tooltips are
+//! not limited to the rect of the `GodotEgui` node.
+//!
+//! egui does not tell which layer a mesh belongs to, but its meshes are sorted from back to front. Everything from
+//! the first mesh that is clipped to a rect outside of the node is drawn into the overlay, which keeps the order of
+//! the meshes intact.
+use gdnative::api::{CanvasLayer, VisualServer};
+use gdnative::prelude::*;
+
+/// The `CanvasLayer` layer of the overlay, above the layers commonly used by games.
+const OVERLAY_LAYER: i64 = 128;
+
+pub(crate) struct PopupOverlay {
+    /// Covers the viewport and stops the mouse events while the pointer is over something drawn in the overlay.
+    control: Ref<Control>,
+    /// The parent of the canvas items drawn in the overlay. It follows the transform of the `GodotEgui` node.
+    canvas_item: Rid,
+}
+
+impl PopupOverlay {
+    /// Creates the overlay as a child of the node. The mouse events received by the overlay are sent to the
+    /// `input_method` of the node.
+    pub(crate) fn new(owner: TRef<Control>, input_method: &str) -> Self {
+        let layer = CanvasLayer::new();
+        layer.set_layer(OVERLAY_LAYER);
+        let control = Control::new();
+        control.set_anchors_preset(Control::PRESET_WIDE, false);
+        control.set_mouse_filter(Control::MOUSE_FILTER_IGNORE);
+        let control = control.into_shared();
+        layer.add_child(control.clone(), false);
+        let layer = layer.into_shared();
+        owner.add_child(layer.clone(), false);
+
+        let result = unsafe { control.assume_safe() }.connect(
+            "gui_input",
+            owner,
+            input_method,
+            VariantArray::new_shared(),
+            0,
+        );
+        if let Err(err) = result {
+            godot_error!("failed to forward the input of the popup overlay: {:?}", err);
+        }
+
+        let vs = unsafe { VisualServer::godot_singleton() };
+        let canvas_item = vs.canvas_item_create();
+        unsafe {
+            vs.canvas_item_set_parent(canvas_item, layer.assume_safe().get_canvas());
+        }
+        Self { control, canvas_item }
+    }
+
+    /// The canvas item that the meshes drawn in the overlay are parented to.
+    pub(crate) fn canvas_item(&self) -> Rid {
+        self.canvas_item
+    }
+
+    /// Moves the overlay to match the node, and decides whether it receives the mouse events.
+    pub(crate) fn update(&self, owner: &Control, wants_pointer: bool) {
+        let vs = unsafe { VisualServer::godot_singleton() };
+        unsafe {
+            vs.canvas_item_set_transform(self.canvas_item, owner.get_global_transform_with_canvas());
+            vs.canvas_item_set_visible(self.canvas_item, owner.is_visible_in_tree());
+        }
+        let mouse_filter = if wants_pointer && owner.is_visible_in_tree() {
+            Control::MOUSE_FILTER_STOP
+        } else {
+            Control::MOUSE_FILTER_IGNORE
+        };
+        unsafe { self.control.assume_safe() }.set_mouse_filter(mouse_filter);
+    }
+
+    /// Frees the canvas item of the overlay. The canvas layer is a child of the node and is freed along with it.
+    pub(crate) fn free_canvas_item(&self) {
+        let vs = unsafe { VisualServer::godot_singleton() };
+        unsafe {
+            vs.free_rid(self.canvas_item);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Resources shared by several `GodotEgui` nodes that set the same `resource_group` name, e.g. the panels of a
+//! HUD. Every node keeps its own `egui::Context`, but the nodes of a group share:
+//! - The registered Godot textures, so a `GodotTextureHandle` created by one node can be drawn by all of them.
+//! - The image cache, so every image is only loaded once. It uses the `image_cache_max_unused_frames` and
+//!   `image_cache_interactive_loading` settings of the first node that joins the group.
+//! - The font definitions and the themes loaded from files, so the font files are only read and parsed once.
+//!
+//! # Limitations
+//! The font atlases are not shared. egui 0.18 builds the atlas inside each `egui::Context`, adding glyphs the first
+//! time they are drawn, and has no way to give several contexts the same `epaint::text::Fonts`. The atlases of two
+//! contexts are therefore not identical and can't be drawn from a single texture. Sharing them would require a
+//! single context for the whole group, so each node still uploads its own font atlas, which is stored with a single
+//! channel to keep it small.
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::image_cache::ImageCache;
+use crate::textures::TextureRegistry;
+
+struct GroupState {
+    name: String,
+    user_textures: Rc<RefCell<TextureRegistry>>,
+    image_cache: ImageCache,
+    /// The `max_unused_frames` and `interactive_loading` settings of the image cache, set by the first node.
+    image_cache_settings: Cell<Option<(u64, bool)>>,
+    fonts: RefCell<Option<egui::FontDefinitions>>,
+    /// Incremented every time the fonts change, so every node knows when to update its context.
+    fonts_generation: Cell<u64>,
+    /// The themes loaded by the nodes of the group, by path.
+    #[cfg(feature = "theme_support")]
+    themes: RefCell<HashMap<String, (egui::Style, egui::FontDefinitions)>>,
+}
+
+thread_local! {
+    static GROUPS: RefCell<HashMap<String, Weak<GroupState>>> = RefCell::new(HashMap::new());
+}
+
+/// A set of resources shared by the `GodotEgui` nodes with the same `resource_group`. The group lives as long as
+/// one of its nodes, or a clone of it, is alive.
+#[derive(Clone)]
+pub struct ResourceGroup {
+    state: Rc<GroupState>,
+}
+
+impl ResourceGroup {
+    /// Returns the group with the given name, creating it if no node uses it yet.
+    pub fn get_or_create(name: &str) -> Self {
+        GROUPS.with(|groups| {
+            let mut groups = groups.borrow_mut();
+            if let Some(state) = groups.get(name).and_then(Weak::upgrade) {
+                return Self { state };
+            }
+            // Forget the groups that are no longer used.
+            groups.retain(|_, state| state.strong_count() > 0);
+
+            let user_textures = Rc::new(RefCell::new(TextureRegistry::default()));
+            let state = Rc::new(GroupState {
+                name: name.to_owned(),
+                image_cache: ImageCache::new(user_textures.clone()),
+                user_textures,
+                image_cache_settings: Cell::new(None),
+                fonts: RefCell::new(None),
+                fonts_generation: Cell::new(0),
+                #[cfg(feature = "theme_support")]
+                themes: RefCell::new(HashMap::new()),
+            });
+            groups.insert(name.to_owned(), Rc::downgrade(&state));
+            Self { state }
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.state.name
+    }
+
+    /// The image cache shared by the nodes of the group.
+    pub fn image_cache(&self) -> ImageCache {
+        self.state.image_cache.clone()
+    }
+
+    /// Configures the shared image cache. Only the settings of the first node that joins the group are used, and
+    /// returns false if they differ from the given ones.
+    pub(crate) fn configure_image_cache(&self, max_unused_frames: u64, interactive_loading: bool) -> bool {
+        let settings = (max_unused_frames, interactive_loading);
+        match self.state.image_cache_settings.get() {
+            Some(current) => current == settings,
+            None => {
+                self.state.image_cache_settings.set(Some(settings));
+                self.state.image_cache.configure(max_unused_frames, interactive_loading);
+                true
+            }
+        }
+    }
+
+    /// Sets the fonts of every node in the group. The nodes switch to them on their next frame.
+    pub fn set_fonts(&self, fonts: egui::FontDefinitions) {
+        *self.state.fonts.borrow_mut() = Some(fonts);
+        self.state.fonts_generation.set(self.state.fonts_generation.get() + 1);
+    }
+
+    /// The fonts shared by the nodes of the group, if any were set.
+    pub fn fonts(&self) -> Option<egui::FontDefinitions> {
+        self.state.fonts.borrow().clone()
+    }
+
+    pub(crate) fn fonts_generation(&self) -> u64 {
+        self.state.fonts_generation.get()
+    }
+
+    pub(crate) fn texture_registry(&self) -> Rc<RefCell<TextureRegistry>> {
+        self.state.user_textures.clone()
+    }
+
+    /// Returns the theme at the given path, only calling `load` the first time a node of the group uses it.
+    #[cfg(feature = "theme_support")]
+    pub(crate) fn theme(
+        &self, path: &str, load: impl FnOnce() -> Result<(egui::Style, egui::FontDefinitions), String>,
+    ) -> Result<(egui::Style, egui::FontDefinitions), String> {
+        if let Some(theme) = self.state.themes.borrow().get(path) {
+            return Ok(theme.clone());
+        }
+        let theme = load()?;
+        self.state.themes.borrow_mut().insert(path.to_owned(), theme.clone());
+        Ok(theme)
+    }
+
+    /// Replaces the cached theme at the given path, after it was reloaded.
+    #[cfg(feature = "theme_support")]
+    pub(crate) fn set_theme(&self, path: &str, theme: (egui::Style, egui::FontDefinitions)) {
+        self.state.themes.borrow_mut().insert(path.to_owned(), theme);
+    }
+}
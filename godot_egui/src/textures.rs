@@ -0,0 +1,474 @@
+//! Types used to manage the Godot textures that are drawn by `GodotEgui`.
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+
+use gdnative::api::{AnimatedTexture, AtlasTexture, ViewportTexture};
+use gdnative::prelude::*;
+
+/// How the pixels of a texture are sampled when it is scaled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFilter {
+    /// Use the closest pixel. Useful for a pixel-art style.
+    Nearest,
+    /// Blend between the neighbouring pixels.
+    Linear,
+}
+
+/// Sampling options of a texture drawn by egui. These map directly to the flags of a Godot `Texture`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureOptions {
+    pub filter: TextureFilter,
+    pub mipmaps: bool,
+    pub repeat: bool,
+}
+
+impl TextureOptions {
+    /// Linear filtering with mipmaps, the default for textures created by egui.
+    pub const LINEAR: Self = Self { filter: TextureFilter::Linear, mipmaps: true, repeat: false };
+    /// Nearest filtering without mipmaps, used when `disable_texture_filtering` is set.
+    pub const NEAREST: Self = Self { filter: TextureFilter::Nearest, mipmaps: false, repeat: false };
+
+    /// Converts the options to `Texture::FLAG_*` flags.
+    pub fn to_flags(self) -> i64 {
+        let mut flags = 0;
+        if self.filter == TextureFilter::Linear {
+            flags |= Texture::FLAG_FILTER;
+        }
+        if self.mipmaps {
+            flags |= Texture::FLAG_MIPMAPS;
+        }
+        if self.repeat {
+            flags |= Texture::FLAG_REPEAT;
+        }
+        flags
+    }
+
+    /// Reads the options from `Texture::FLAG_*` flags.
+    pub fn from_flags(flags: i64) -> Self {
+        Self {
+            filter: if flags & Texture::FLAG_FILTER != 0 { TextureFilter::Linear } else { TextureFilter::Nearest },
+            mipmaps: flags & Texture::FLAG_MIPMAPS != 0,
+            repeat: flags & Texture::FLAG_REPEAT != 0,
+        }
+    }
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self::LINEAR
+    }
+}
+
+/// Converts a godot `Rid` into the `egui::TextureId` used to draw it. Returns `None` for an empty `Rid`, e.g. the
+/// rid of an `ImageTexture` without an image.
+pub(crate) fn rid_to_texture_id(rid: Rid) -> Option<egui::TextureId> {
+    if !rid.is_occupied() {
+        return None;
+    }
+    // Safety: the rid is not empty, and callers pass the rid of a texture they hold a reference to.
+    Some(egui::TextureId::User(unsafe { rid.get_id() } as u32 as u64))
+}
+
+/// A rectangular part of a texture that can be drawn with egui. Regions are created from a
+/// `GodotTextureHandle`, which resolves the region and margin of an `AtlasTexture` automatically.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextureRegion {
+    pub texture_id: egui::TextureId,
+    /// The normalized uv coordinates of the region inside the texture.
+    pub uv: egui::Rect,
+    /// The size of the region in pixels, including the margin of an `AtlasTexture`.
+    pub size: egui::Vec2,
+    /// Where the texture content is drawn inside `size`, normalized to `0..1`. Anything outside of it is the
+    /// empty margin of an `AtlasTexture`.
+    pub content: egui::Rect,
+}
+
+impl TextureRegion {
+    /// The region covering a whole texture.
+    pub fn full(texture_id: egui::TextureId, size: egui::Vec2) -> Self {
+        let unit = egui::Rect::from_min_max(egui::Pos2::ZERO, egui::pos2(1.0, 1.0));
+        Self { texture_id, uv: unit, size, content: unit }
+    }
+
+    /// Resolves the region of a texture. For an `AtlasTexture` this is the `region` inside its atlas, padded by its
+    /// `margin`.
+    pub fn from_texture(texture_id: egui::TextureId, texture: TRef<Texture>) -> Self {
+        let size = texture.get_size();
+        let full = Self::full(texture_id, egui::vec2(size.x, size.y));
+        let atlas_texture = match texture.cast::<AtlasTexture>() {
+            Some(atlas_texture) => atlas_texture,
+            None => return full,
+        };
+        let atlas_size = match atlas_texture.atlas() {
+            Some(atlas) => unsafe { atlas.assume_safe() }.get_size(),
+            None => return full,
+        };
+        let region = atlas_texture.region();
+        // An empty region means the whole atlas is used
+        if region.size.x <= 0.0 || region.size.y <= 0.0 || atlas_size.x <= 0.0 || atlas_size.y <= 0.0 {
+            return full;
+        }
+        let margin = atlas_texture.margin();
+        let total = region.size + margin.size;
+        Self {
+            texture_id,
+            uv: egui::Rect::from_min_max(
+                egui::pos2(region.position.x / atlas_size.x, region.position.y / atlas_size.y),
+                egui::pos2(
+                    (region.position.x + region.size.x) / atlas_size.x,
+                    (region.position.y + region.size.y) / atlas_size.y,
+                ),
+            ),
+            size: egui::vec2(total.x, total.y),
+            content: egui::Rect::from_min_size(
+                egui::pos2(margin.position.x / total.x, margin.position.y / total.y),
+                egui::vec2(region.size.x / total.x, region.size.y / total.y),
+            ),
+        }
+    }
+
+    /// A part of this region, given in pixels relative to the top left corner of its content.
+    pub fn sub_region(&self, rect: Rect2) -> Self {
+        let content_size = self.size * self.content.size();
+        let to_uv = |x: f32, y: f32| {
+            egui::pos2(
+                self.uv.min.x + self.uv.width() * x / content_size.x,
+                self.uv.min.y + self.uv.height() * y / content_size.y,
+            )
+        };
+        let uv = egui::Rect::from_min_max(
+            to_uv(rect.position.x, rect.position.y),
+            to_uv(rect.position.x + rect.size.x, rect.position.y + rect.size.y),
+        );
+        Self { uv, ..Self::full(self.texture_id, egui::vec2(rect.size.x, rect.size.y)) }
+    }
+
+    /// A single frame of a sprite sheet with `hframes` columns and `vframes` rows, counting frames from left to
+    /// right and top to bottom, like `Sprite::frame`.
+    pub fn frame(&self, hframes: u32, vframes: u32, index: u32) -> Self {
+        let (hframes, vframes) = (hframes.max(1), vframes.max(1));
+        let content_size = self.size * self.content.size();
+        let frame_size = Vector2::new(content_size.x / hframes as f32, content_size.y / vframes as f32);
+        let (column, row) = (index % hframes, (index / hframes) % vframes);
+        self.sub_region(Rect2 {
+            position: Vector2::new(column as f32 * frame_size.x, row as f32 * frame_size.y),
+            size: frame_size,
+        })
+    }
+
+    /// Builds an `egui::Image` showing this region. The margin of an `AtlasTexture` is not drawn, use `show` to
+    /// keep it.
+    pub fn image(&self, size: impl Into<egui::Vec2>) -> egui::Image {
+        egui::Image::new(self.texture_id, size).uv(self.uv)
+    }
+
+    /// Builds an `egui::ImageButton` showing this region. The margin of an `AtlasTexture` is not drawn.
+    pub fn image_button(&self, size: impl Into<egui::Vec2>) -> egui::ImageButton {
+        egui::ImageButton::new(self.texture_id, size).uv(self.uv)
+    }
+
+    /// Shows this region at the given size, keeping the empty margin of an `AtlasTexture` around it.
+    pub fn show(&self, ui: &mut egui::Ui, size: impl Into<egui::Vec2>) -> egui::Response {
+        let (rect, response) = ui.allocate_exact_size(size.into(), egui::Sense::hover());
+        if ui.is_rect_visible(rect) {
+            let content_rect = egui::Rect::from_min_max(
+                rect.min + self.content.min.to_vec2() * rect.size(),
+                rect.min + self.content.max.to_vec2() * rect.size(),
+            );
+            self.image(content_rect.size()).paint_at(ui, content_rect);
+        }
+        response
+    }
+}
+
+/// A Godot texture registered with `GodotEgui`, along with the number of handles that keep it alive.
+struct RegisteredTexture {
+    texture: Ref<Texture>,
+    handle_count: usize,
+    /// Tells apart registrations of the same texture, so handles left over from a registration that was removed
+    /// with `unregister_godot_texture` don't release a newer one.
+    generation: u64,
+    /// Whether the texture is an `AnimatedTexture` or `ViewportTexture`, whose frame and size are watched.
+    watched: bool,
+    /// Set by the user for textures that change in ways that can't be detected. egui repaints every frame while
+    /// they are drawn.
+    animated: bool,
+    /// The frame and size of a watched texture when it was last checked.
+    last_state: Option<(i64, Vector2)>,
+}
+
+impl RegisteredTexture {
+    fn new(texture: Ref<Texture>, generation: u64) -> Self {
+        let watched = {
+            let texture = unsafe { texture.assume_safe() };
+            texture.cast::<AnimatedTexture>().is_some() || texture.cast::<ViewportTexture>().is_some()
+        };
+        Self { texture, handle_count: 0, generation, watched, animated: false, last_state: None }
+    }
+
+    /// Returns true if the texture looks different than when it was last checked.
+    fn has_changed(&mut self) -> bool {
+        if self.animated {
+            return true;
+        }
+        if !self.watched {
+            return false;
+        }
+        let texture = unsafe { self.texture.assume_safe() };
+        let frame = texture.cast::<AnimatedTexture>().map_or(-1, |animated| animated.current_frame());
+        let state = Some((frame, texture.get_size()));
+        let changed = self.last_state.is_some() && self.last_state != state;
+        self.last_state = state;
+        changed
+    }
+}
+
+/// Information about a registered texture, used to debug textures that are never unregistered.
+#[derive(Clone)]
+pub struct RegisteredTextureInfo {
+    pub texture_id: egui::TextureId,
+    pub texture: Ref<Texture>,
+    /// The number of `GodotTextureHandle`s that currently reference the texture.
+    pub handle_count: usize,
+}
+
+/// Keeps track of the Godot textures that egui is allowed to draw.
+#[derive(Default)]
+pub struct TextureRegistry {
+    textures: HashMap<egui::TextureId, RegisteredTexture>,
+    next_generation: u64,
+}
+
+impl TextureRegistry {
+    /// Adds a texture to the registry, or adds a handle to an existing one. Returns the id of the texture and the
+    /// generation of its registration, or `None` if the texture has an empty rid.
+    pub(crate) fn register(&mut self, texture: Ref<Texture>) -> Option<(egui::TextureId, u64)> {
+        let texture_id = rid_to_texture_id(unsafe { texture.assume_safe() }.get_rid())?;
+        let next_generation = &mut self.next_generation;
+        let registered = self.textures.entry(texture_id).or_insert_with(|| {
+            *next_generation += 1;
+            RegisteredTexture::new(texture, *next_generation)
+        });
+        registered.handle_count += 1;
+        Some((texture_id, registered.generation))
+    }
+
+    /// Adds a handle to a texture, if it is still registered with the given generation.
+    pub(crate) fn retain(&mut self, texture_id: egui::TextureId, generation: u64) {
+        if let Some(registered) = self.textures.get_mut(&texture_id) {
+            if registered.generation == generation {
+                registered.handle_count += 1;
+            }
+        }
+    }
+
+    /// Removes a handle from a texture, unregistering the texture when no handles are left. Handles of an older
+    /// registration of the texture are ignored.
+    pub(crate) fn release(&mut self, texture_id: egui::TextureId, generation: u64) {
+        if let Some(registered) = self.textures.get_mut(&texture_id) {
+            if registered.generation != generation {
+                return;
+            }
+            registered.handle_count = registered.handle_count.saturating_sub(1);
+            if registered.handle_count == 0 {
+                self.textures.remove(&texture_id);
+            }
+        }
+    }
+
+    /// Unregisters a texture regardless of how many handles reference it.
+    pub(crate) fn remove(&mut self, texture_id: egui::TextureId) -> Option<Ref<Texture>> {
+        self.textures.remove(&texture_id).map(|registered| registered.texture)
+    }
+
+    pub(crate) fn get(&self, texture_id: &egui::TextureId) -> Option<&Ref<Texture>> {
+        self.textures.get(texture_id).map(|registered| &registered.texture)
+    }
+
+    /// Marks a texture as changing every frame, so egui keeps repainting while it is drawn. Returns false if the
+    /// texture is not registered.
+    pub(crate) fn set_animated(&mut self, texture_id: egui::TextureId, animated: bool) -> bool {
+        match self.textures.get_mut(&texture_id) {
+            Some(registered) => {
+                registered.animated = animated;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Checks whether any of the drawn textures changed since the last call. `AnimatedTexture`s are checked for
+    /// a new frame and `ViewportTexture`s for a new size, while textures marked as animated always count as changed.
+    pub(crate) fn poll_changes(&mut self, drawn: &HashSet<egui::TextureId>) -> bool {
+        let mut changed = false;
+        for texture_id in drawn {
+            if let Some(registered) = self.textures.get_mut(texture_id) {
+                changed |= registered.has_changed();
+            }
+        }
+        changed
+    }
+
+    /// The number of textures currently registered.
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    /// Lists every registered texture and how many handles reference it.
+    pub fn info(&self) -> Vec<RegisteredTextureInfo> {
+        self.textures
+            .iter()
+            .map(|(texture_id, registered)| RegisteredTextureInfo {
+                texture_id: *texture_id,
+                texture: registered.texture.clone(),
+                handle_count: registered.handle_count,
+            })
+            .collect()
+    }
+}
+
+/// A handle to a Godot texture registered with `GodotEgui::register_godot_texture`. The handle keeps the texture
+/// resource alive and the texture is unregistered once every handle to it has been dropped.
+///
+/// An `AtlasTexture` is drawn using its atlas, so use `region`, `image` or `image_button` rather than
+/// `texture_id` alone to only draw its part of the atlas.
+#[must_use = "the texture is unregistered as soon as the handle is dropped"]
+pub struct GodotTextureHandle {
+    texture: Ref<Texture>,
+    texture_id: egui::TextureId,
+    generation: u64,
+    registry: Weak<RefCell<TextureRegistry>>,
+}
+
+impl GodotTextureHandle {
+    /// Registers `texture`. Returns `None` if the texture has an empty rid, e.g. an `ImageTexture` without an image.
+    pub(crate) fn new(registry: &Rc<RefCell<TextureRegistry>>, texture: Ref<Texture>) -> Option<Self> {
+        let (texture_id, generation) = registry.borrow_mut().register(texture.clone())?;
+        Some(Self { texture, texture_id, generation, registry: Rc::downgrade(registry) })
+    }
+
+    /// The id used to draw this texture with egui, e.g. with `egui::Ui::image`.
+    pub fn texture_id(&self) -> egui::TextureId {
+        self.texture_id
+    }
+
+    /// The Godot texture this handle refers to.
+    pub fn texture(&self) -> &Ref<Texture> {
+        &self.texture
+    }
+
+    /// Marks the texture as changing every frame, so egui keeps repainting while it is drawn even when
+    /// `reactive_update` is enabled. `AnimatedTexture`s and `ViewportTexture`s are detected automatically.
+    pub fn set_animated(&self, animated: bool) {
+        if let Some(registry) = self.registry.upgrade() {
+            registry.borrow_mut().set_animated(self.texture_id, animated);
+        }
+    }
+
+    /// The current size of the texture in pixels. For an `AtlasTexture` this is the size of its region and margin.
+    /// This is read from the texture every time, so it follows a `ViewportTexture` when its viewport is resized.
+    pub fn size(&self) -> egui::Vec2 {
+        let size = unsafe { self.texture.assume_safe() }.get_size();
+        egui::vec2(size.x, size.y)
+    }
+
+    /// The part of the texture this handle draws. For an `AtlasTexture` only its region of the atlas is drawn.
+    pub fn region(&self) -> TextureRegion {
+        TextureRegion::from_texture(self.texture_id, unsafe { self.texture.assume_safe() })
+    }
+
+    /// A part of the texture, given in pixels. See `TextureRegion::sub_region`.
+    pub fn sub_region(&self, rect: Rect2) -> TextureRegion {
+        self.region().sub_region(rect)
+    }
+
+    /// A single frame of a sprite sheet. See `TextureRegion::frame`.
+    pub fn frame(&self, hframes: u32, vframes: u32, index: u32) -> TextureRegion {
+        self.region().frame(hframes, vframes, index)
+    }
+
+    /// Builds an `egui::Image` showing the texture, or the region of an `AtlasTexture`.
+    pub fn image(&self, size: impl Into<egui::Vec2>) -> egui::Image {
+        self.region().image(size)
+    }
+
+    /// Builds an `egui::ImageButton` showing the texture, or the region of an `AtlasTexture`.
+    pub fn image_button(&self, size: impl Into<egui::Vec2>) -> egui::ImageButton {
+        self.region().image_button(size)
+    }
+}
+
+impl Clone for GodotTextureHandle {
+    fn clone(&self) -> Self {
+        if let Some(registry) = self.registry.upgrade() {
+            registry.borrow_mut().retain(self.texture_id, self.generation);
+        }
+        Self {
+            texture: self.texture.clone(),
+            texture_id: self.texture_id,
+            generation: self.generation,
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+impl Drop for GodotTextureHandle {
+    fn drop(&mut self) {
+        if let Some(registry) = self.registry.upgrade() {
+            registry.borrow_mut().release(self.texture_id, self.generation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect2 {
+        Rect2 { position: Vector2::new(x, y), size: Vector2::new(width, height) }
+    }
+
+    fn uv(min: (f32, f32), max: (f32, f32)) -> egui::Rect {
+        egui::Rect::from_min_max(egui::pos2(min.0, min.1), egui::pos2(max.0, max.1))
+    }
+
+    #[test]
+    fn sub_region_of_full_texture() {
+        let region = TextureRegion::full(egui::TextureId::User(1), egui::vec2(64.0, 32.0));
+        let sub_region = region.sub_region(rect(16.0, 8.0, 16.0, 8.0));
+        assert_eq!(sub_region.uv, uv((0.25, 0.25), (0.5, 0.5)));
+        assert_eq!(sub_region.size, egui::vec2(16.0, 8.0));
+        assert_eq!(sub_region.content, uv((0.0, 0.0), (1.0, 1.0)));
+    }
+
+    #[test]
+    fn sub_region_of_atlas_region_ignores_margin() {
+        // The right half of the top row of a 128x128 atlas, with a 32 pixel margin on every side.
+        let region = TextureRegion {
+            texture_id: egui::TextureId::User(1),
+            uv: uv((0.5, 0.0), (1.0, 0.5)),
+            size: egui::vec2(128.0, 128.0),
+            content: uv((0.25, 0.25), (0.75, 0.75)),
+        };
+        let sub_region = region.sub_region(rect(32.0, 0.0, 32.0, 32.0));
+        assert_eq!(sub_region.uv, uv((0.75, 0.0), (1.0, 0.25)));
+        assert_eq!(sub_region.size, egui::vec2(32.0, 32.0));
+    }
+
+    #[test]
+    fn frame_counts_left_to_right_then_top_to_bottom() {
+        let region = TextureRegion::full(egui::TextureId::User(1), egui::vec2(40.0, 20.0));
+        let frame = region.frame(4, 2, 5);
+        assert_eq!(frame.uv, uv((0.25, 0.5), (0.5, 1.0)));
+        assert_eq!(frame.size, egui::vec2(10.0, 10.0));
+        // Indices past the last frame wrap around.
+        assert_eq!(region.frame(4, 2, 9), region.frame(4, 2, 1));
+        // Zero columns or rows are treated as one.
+        assert_eq!(region.frame(0, 0, 3).uv, uv((0.0, 0.0), (1.0, 1.0)));
+    }
+}
@@ -0,0 +1,180 @@
+//! `.eguitheme` files as Godot resources. Requires the `theme_support` feature.
+//!
+//! `GodotEguiThemeLoader` and `GodotEguiThemeSaver` let Godot load and save `.eguitheme` files as `GodotEguiTheme`
+//! resources, so they appear in the FileSystem dock, are included in exported builds, and can be loaded with
+//! `load` and `preload` from GDScript. Godot registers them from their `.gdns` files, which must set
+//! `script_class_name` to the name of the class.
+use gdnative::api::{File, ResourceFormatLoader, ResourceFormatSaver, Theme};
+use gdnative::prelude::*;
+
+use crate::godot_theme;
+
+/// The extension of egui theme files.
+const EXTENSION: &str = "eguitheme";
+
+/// An egui style and fonts, loaded from an `.eguitheme` file. Set it as the `theme_resource` of a `GodotEgui` node.
+#[derive(NativeClass)]
+#[inherit(Resource)]
+pub struct GodotEguiTheme {
+    style: egui::Style,
+    font_definitions: egui::FontDefinitions,
+}
+
+#[methods]
+impl GodotEguiTheme {
+    fn new(_owner: &Resource) -> Self {
+        Self { style: Default::default(), font_definitions: Default::default() }
+    }
+
+    /// Creates a theme resource from a style and fonts.
+    pub fn from_style(style: egui::Style, font_definitions: egui::FontDefinitions) -> Instance<Self, Unique> {
+        Instance::emplace(Self { style, font_definitions })
+    }
+
+    pub fn style(&self) -> &egui::Style {
+        &self.style
+    }
+
+    pub fn font_definitions(&self) -> &egui::FontDefinitions {
+        &self.font_definitions
+    }
+
+    pub fn set_style(&mut self, style: egui::Style) {
+        self.style = style;
+    }
+
+    pub fn set_font_definitions(&mut self, font_definitions: egui::FontDefinitions) {
+        self.font_definitions = font_definitions;
+    }
+
+    /// Creates a Godot `Theme` that matches this theme, see `godot_theme::style_to_godot_theme`.
+    #[export]
+    pub fn to_godot_theme(&self, _owner: &Resource) -> Ref<Theme, Unique> {
+        godot_theme::style_to_godot_theme(&self.style)
+    }
+}
+
+/// Loads `.eguitheme` files as `GodotEguiTheme` resources.
+#[derive(NativeClass)]
+#[inherit(ResourceFormatLoader)]
+pub struct GodotEguiThemeLoader;
+
+#[methods]
+impl GodotEguiThemeLoader {
+    fn new(_owner: &ResourceFormatLoader) -> Self {
+        Self
+    }
+
+    #[export]
+    fn get_recognized_extensions(&self, _owner: &ResourceFormatLoader) -> StringArray {
+        let mut extensions = StringArray::new();
+        extensions.push(EXTENSION.into());
+        extensions
+    }
+
+    #[export]
+    fn handles_type(&self, _owner: &ResourceFormatLoader, type_name: String) -> bool {
+        type_name == "Resource"
+    }
+
+    #[export]
+    fn get_resource_type(&self, _owner: &ResourceFormatLoader, path: String) -> String {
+        if has_extension(&path) {
+            "Resource".to_owned()
+        } else {
+            String::new()
+        }
+    }
+
+    #[export]
+    fn load(&self, _owner: &ResourceFormatLoader, path: String, _original_path: String) -> Variant {
+        match load_egui_theme(&path) {
+            Ok((style, font_definitions)) => {
+                GodotEguiTheme::from_style(style, font_definitions).into_base().owned_to_variant()
+            }
+            Err(error) => {
+                godot_error!("{}", error);
+                (GodotError::FileCorrupt as i64).to_variant()
+            }
+        }
+    }
+}
+
+/// Saves `GodotEguiTheme` resources as `.eguitheme` files.
+#[derive(NativeClass)]
+#[inherit(ResourceFormatSaver)]
+pub struct GodotEguiThemeSaver;
+
+#[methods]
+impl GodotEguiThemeSaver {
+    fn new(_owner: &ResourceFormatSaver) -> Self {
+        Self
+    }
+
+    #[export]
+    fn get_recognized_extensions(&self, _owner: &ResourceFormatSaver, resource: Ref<Resource>) -> StringArray {
+        let mut extensions = StringArray::new();
+        if is_egui_theme(&resource) {
+            extensions.push(EXTENSION.into());
+        }
+        extensions
+    }
+
+    #[export]
+    fn recognize(&self, _owner: &ResourceFormatSaver, resource: Ref<Resource>) -> bool {
+        is_egui_theme(&resource)
+    }
+
+    #[export]
+    fn save(&self, _owner: &ResourceFormatSaver, path: String, resource: Ref<Resource>, _flags: i64) -> i64 {
+        let resource = unsafe { resource.assume_safe() };
+        let theme = resource
+            .cast_instance::<GodotEguiTheme>()
+            .and_then(|theme| theme.map(|theme, _| (theme.style.clone(), theme.font_definitions.clone())).ok());
+        let (style, font_definitions) = match theme {
+            Some(theme) => theme,
+            None => return GodotError::InvalidParameter as i64,
+        };
+        match godot_theme::save_egui_theme(&path, style, font_definitions) {
+            Ok(()) => 0,
+            Err(error) => {
+                godot_error!("{}", error);
+                GodotError::FileCantWrite as i64
+            }
+        }
+    }
+}
+
+/// Reads and deserializes the `.eguitheme` or `.ron` theme file at `path`.
+pub(crate) fn load_egui_theme(path: &str) -> Result<(egui::Style, egui::FontDefinitions), String> {
+    let file = File::new();
+    if !file.file_exists(path) {
+        return Err(format!("file {} does not exist", path));
+    }
+    if let Err(error) = file.open(path, File::READ) {
+        return Err(format!("{}", error));
+    }
+    let file_data = file.get_as_text();
+    file.close();
+    match ron::from_str::<egui_theme::EguiTheme>(file_data.to_string().as_str()) {
+        Ok(theme) => Ok(theme.extract()),
+        Err(err) => Err(format!("Theme could not be deserialized due to: {:#?}", err)),
+    }
+}
+
+/// Returns the style and fonts of a `GodotEguiTheme` resource, or `None` if the resource is of another type.
+pub(crate) fn theme_from_resource(resource: &Ref<Resource>) -> Option<(egui::Style, egui::FontDefinitions)> {
+    let resource = unsafe { resource.assume_safe() };
+    let theme = resource.cast_instance::<GodotEguiTheme>()?;
+    theme.map(|theme, _| (theme.style.clone(), theme.font_definitions.clone())).ok()
+}
+
+fn is_egui_theme(resource: &Ref<Resource>) -> bool {
+    unsafe { resource.assume_safe() }.cast_instance::<GodotEguiTheme>().is_some()
+}
+
+fn has_extension(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .map_or(false, |extension| extension.to_string_lossy().eq_ignore_ascii_case(EXTENSION))
+}
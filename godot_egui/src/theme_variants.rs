@@ -0,0 +1,138 @@
+//! Named themes that a `GodotEgui` node can switch between at runtime, e.g. light, dark and high contrast
+//! variants.
+//!
+//! The `themes` dictionary of the node maps names to themes. With the `theme_support` feature, a theme is the path
+//! of an `.eguitheme` file or a `GodotEguiTheme` resource. `light` and `dark` are built in, using egui's default
+//! visuals, unless the dictionary defines them.
+//!
+//! Switching themes can animate the colors of the old visuals into the new ones. Everything else, such as the
+//! spacing and text styles, is changed at once.
+use gdnative::api::OS;
+use gdnative::prelude::*;
+
+/// The name of the theme used when the OS is in dark mode.
+pub(crate) const DARK: &str = "dark";
+/// The name of the theme used when the OS is in light mode, or doesn't report it.
+pub(crate) const LIGHT: &str = "light";
+
+/// A color animation started by `GodotEgui::set_active_theme`
+pub(crate) struct ThemeTransition {
+    from: egui::Visuals,
+    to: egui::Visuals,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl ThemeTransition {
+    pub fn new(from: egui::Visuals, to: egui::Visuals, duration: f32) -> Self {
+        Self { from, to, duration, elapsed: 0.0 }
+    }
+
+    /// Advances the animation by `delta` seconds. Returns the visuals to draw with and whether it has finished.
+    pub fn advance(&mut self, delta: f32) -> (egui::Visuals, bool) {
+        self.elapsed += delta;
+        let t = (self.elapsed / self.duration).min(1.0);
+        (lerp_visuals(&self.from, &self.to, t), t >= 1.0)
+    }
+}
+
+/// Reads the theme called `name` from `themes`, falling back to the built-in `light` and `dark` themes. The fonts
+/// are `None` when the theme doesn't change them. `base` is the current style, which the built-in themes keep
+/// everything but the visuals of.
+pub(crate) fn theme_by_name(
+    themes: &Dictionary, name: &str, base: &egui::Style,
+) -> Result<(egui::Style, Option<egui::FontDefinitions>), String> {
+    if let Some(theme) = themes.get(name) {
+        return theme_from_variant(&theme).map_err(|error| format!("the theme {} can't be used: {}", name, error));
+    }
+    let visuals = match name {
+        DARK => egui::Visuals::dark(),
+        LIGHT => egui::Visuals::light(),
+        _ => return Err(format!("there is no theme called {}", name)),
+    };
+    Ok((egui::Style { visuals, ..base.clone() }, None))
+}
+
+#[cfg(feature = "theme_support")]
+fn theme_from_variant(theme: &Variant) -> Result<(egui::Style, Option<egui::FontDefinitions>), String> {
+    if let Ok(path) = theme.try_to::<String>() {
+        let (style, fonts) = crate::theme_resource::load_egui_theme(&path)?;
+        return Ok((style, Some(fonts)));
+    }
+    let resource =
+        theme.try_to::<Ref<Resource>>().map_err(|_| "expected a path or a GodotEguiTheme".to_owned())?;
+    match crate::theme_resource::theme_from_resource(&resource) {
+        Some((style, fonts)) => Ok((style, Some(fonts))),
+        None => Err("the resource is not a GodotEguiTheme".to_owned()),
+    }
+}
+
+#[cfg(not(feature = "theme_support"))]
+fn theme_from_variant(_theme: &Variant) -> Result<(egui::Style, Option<egui::FontDefinitions>), String> {
+    Err("themes other than light and dark require the theme_support feature of godot_egui".to_owned())
+}
+
+/// Whether the running version of Godot can report the dark mode of the OS. Godot 3, which this crate targets,
+/// doesn't have `OS.is_dark_mode`, so this is only true on engines that add it.
+pub(crate) fn engine_reports_dark_mode() -> bool {
+    let os = OS::godot_singleton();
+    os.has_method("is_dark_mode_supported") && os.has_method("is_dark_mode")
+}
+
+/// Whether the OS is in dark mode, or `None` if the running version of Godot or the platform doesn't report it.
+pub(crate) fn os_dark_mode() -> Option<bool> {
+    if !engine_reports_dark_mode() {
+        return None;
+    }
+    let os = OS::godot_singleton();
+    // Called by name, since these methods are missing from older versions of Godot.
+    let supported = unsafe { os.call("is_dark_mode_supported", &[]) };
+    if !supported.try_to::<bool>().unwrap_or(false) {
+        return None;
+    }
+    unsafe { os.call("is_dark_mode", &[]) }.try_to::<bool>().ok()
+}
+
+/// Interpolates the colors, strokes and shadows of two visuals. The other settings are taken from `to`.
+pub(crate) fn lerp_visuals(from: &egui::Visuals, to: &egui::Visuals, t: f32) -> egui::Visuals {
+    let mut visuals = to.clone();
+    if let (Some(from_color), Some(to_color)) = (from.override_text_color, to.override_text_color) {
+        visuals.override_text_color = Some(lerp_color(from_color, to_color, t));
+    }
+    for (widget, from_widget) in [
+        (&mut visuals.widgets.noninteractive, &from.widgets.noninteractive),
+        (&mut visuals.widgets.inactive, &from.widgets.inactive),
+        (&mut visuals.widgets.hovered, &from.widgets.hovered),
+        (&mut visuals.widgets.active, &from.widgets.active),
+        (&mut visuals.widgets.open, &from.widgets.open),
+    ]
+    .iter_mut()
+    {
+        widget.bg_fill = lerp_color(from_widget.bg_fill, widget.bg_fill, t);
+        widget.bg_stroke = lerp_stroke(from_widget.bg_stroke, widget.bg_stroke, t);
+        widget.fg_stroke = lerp_stroke(from_widget.fg_stroke, widget.fg_stroke, t);
+    }
+    visuals.selection.bg_fill = lerp_color(from.selection.bg_fill, to.selection.bg_fill, t);
+    visuals.selection.stroke = lerp_stroke(from.selection.stroke, to.selection.stroke, t);
+    visuals.hyperlink_color = lerp_color(from.hyperlink_color, to.hyperlink_color, t);
+    visuals.faint_bg_color = lerp_color(from.faint_bg_color, to.faint_bg_color, t);
+    visuals.extreme_bg_color = lerp_color(from.extreme_bg_color, to.extreme_bg_color, t);
+    visuals.code_bg_color = lerp_color(from.code_bg_color, to.code_bg_color, t);
+    visuals.window_shadow.color = lerp_color(from.window_shadow.color, to.window_shadow.color, t);
+    visuals.popup_shadow.color = lerp_color(from.popup_shadow.color, to.popup_shadow.color, t);
+    visuals
+}
+
+fn lerp_stroke(from: egui::Stroke, to: egui::Stroke, t: f32) -> egui::Stroke {
+    egui::Stroke::new(egui::lerp(from.width..=to.width, t), lerp_color(from.color, to.color, t))
+}
+
+fn lerp_color(from: egui::Color32, to: egui::Color32, t: f32) -> egui::Color32 {
+    let channel = |from: u8, to: u8| egui::lerp(from as f32..=to as f32, t).round() as u8;
+    egui::Color32::from_rgba_premultiplied(
+        channel(from.r(), to.r()),
+        channel(from.g(), to.g()),
+        channel(from.b(), to.b()),
+        channel(from.a(), to.a()),
+    )
+}